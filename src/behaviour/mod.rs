@@ -0,0 +1,133 @@
+use crate::*;
+use commands::Context;
+use physics::Contact;
+
+/// Stateful script attached to a SimObject, every hook is optional.
+/// The object the behaviour is attached to is passed in, anything else goes through the context
+pub trait Behaviour: BehaviourClone {
+    /// Runs once before the first update after the behaviour was added
+    fn on_start(&mut self, _object: &mut SimObject, _context: &mut Context) {}
+
+    fn on_update(&mut self, _object: &mut SimObject, _context: &mut Context) {}
+
+    fn on_fixed_update(&mut self, _object: &mut SimObject, _context: &mut Context) {}
+
+    /// `other` is the id of the object that was hit, contacts come from collision passes such as gas containers
+    fn on_collision(&mut self, _object: &mut SimObject, _other: u128, _contact: &Contact, _context: &mut Context) {}
+
+    /// Runs when the object is removed from the simulation
+    fn on_destroy(&mut self, _object: &mut SimObject, _context: &mut Context) {}
+}
+
+/// Implemented for every behaviour that is Clone
+pub trait BehaviourClone {
+    fn clone_box(&self) -> Box<dyn Behaviour>;
+}
+
+impl<T: Behaviour + Clone + 'static> BehaviourClone for T {
+    fn clone_box(&self) -> Box<dyn Behaviour> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Behaviour> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Calls `hook` on every behaviour of the object. Behaviours are taken out while they run
+/// so they can borrow the object, ones added during the hook are kept
+pub(crate) fn run(object: &mut SimObject, context: &mut Context, mut hook: impl FnMut(&mut dyn Behaviour, &mut SimObject, &mut Context)) {
+    if object.behaviours.is_empty() { return; }
+
+    let mut behaviours = std::mem::take(&mut object.behaviours);
+    for behaviour in behaviours.iter_mut() {
+        hook(behaviour.as_mut(), object, context);
+    }
+    behaviours.append(&mut object.behaviours);
+    object.behaviours = behaviours;
+}
+
+/// Starts behaviours that haven't run yet
+pub(crate) fn start(object: &mut SimObject, context: &mut Context) {
+    while object.started_behaviours < object.behaviours.len() {
+        let mut behaviour = object.behaviours.remove(object.started_behaviours);
+        behaviour.on_start(object, context);
+        object.behaviours.insert(object.started_behaviours, behaviour);
+        object.started_behaviours += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use physics::Physics;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static DESTROYED: AtomicUsize = AtomicUsize::new(0);
+
+    // Shows its count through the rotation, which nothing else touches
+    #[derive(Clone)]
+    struct Counter {
+        count: u32,
+    }
+
+    impl Behaviour for Counter {
+        fn on_fixed_update(&mut self, object: &mut SimObject, _context: &mut Context) {
+            self.count += 1;
+            object.transform.rotation.degrees = self.count as f64;
+        }
+
+        fn on_destroy(&mut self, _object: &mut SimObject, _context: &mut Context) {
+            DESTROYED.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn counter_object(name: &str) -> SimObject {
+        let mut object = SimObject::new(0.0, 0.0, 0.0, None, None, None, None).with_name(name);
+        object.add_behaviour(Counter { count: 0 });
+        object
+    }
+
+    fn count(simulation: &Simulation, name: &str) -> f64 {
+        simulation.objects.get(simulation.find_by_name(name).unwrap()).unwrap().transform.rotation.degrees
+    }
+
+    #[test]
+    fn restore_rewinds_behaviours() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::ZERO)).unwrap();
+        simulation.add_object(counter_object("counter")).unwrap();
+        simulation.step_once();
+        let snapshot = simulation.snapshot();
+
+        simulation.step_once();
+        simulation.step_once();
+        assert_eq!(count(&simulation, "counter"), 3.0);
+
+        simulation.restore(&snapshot).unwrap();
+        simulation.step_once();
+        assert_eq!(count(&simulation, "counter"), 2.0);
+    }
+
+    #[test]
+    fn spawn_keeps_behaviours() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::ZERO)).unwrap();
+        simulation.spawn(counter_object("spawned")).unwrap();
+        simulation.step_once();
+        simulation.step_once();
+        assert_eq!(count(&simulation, "spawned"), 2.0);
+    }
+
+    #[test]
+    fn objects_missing_from_a_restore_are_destroyed() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::ZERO)).unwrap();
+        let snapshot = simulation.snapshot();
+        simulation.add_object(counter_object("late")).unwrap();
+
+        let before = DESTROYED.load(Ordering::SeqCst);
+        simulation.restore(&snapshot).unwrap();
+        assert_eq!(DESTROYED.load(Ordering::SeqCst), before + 1);
+        assert!(simulation.find_by_name("late").is_none());
+    }
+}
@@ -0,0 +1,52 @@
+use std::time;
+
+use crate::*;
+
+/// Queued by scripts, applied by `Simulation` once every object has been updated
+pub enum Command {
+    Spawn(Box<SimObject>),
+    Despawn(u128),
+    ApplyForce {
+        id: u128,
+        force: Vector,
+    },
+    SetGravity(Vector),
+}
+
+/// Passed to `update` and `fixed_update` callbacks, nothing happens until the callbacks have all run
+pub struct Context {
+    // Time since the last update, or the fixed step inside fixed updates
+    pub delta_time: time::Duration,
+    commands: Vec<Command>,
+}
+
+impl Context {
+    pub fn new(delta_time: time::Duration) -> Context {
+        Context {
+            delta_time,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn spawn(&mut self, object: SimObject) {
+        self.commands.push(Command::Spawn(Box::new(object)));
+    }
+
+    /// Works for the calling object too
+    pub fn despawn(&mut self, id: u128) {
+        self.commands.push(Command::Despawn(id));
+    }
+
+    /// Acts for the next physics step only
+    pub fn apply_force(&mut self, id: u128, force: Vector) {
+        self.commands.push(Command::ApplyForce { id, force });
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vector) {
+        self.commands.push(Command::SetGravity(gravity));
+    }
+
+    pub fn take_commands(&mut self) -> Vec<Command> {
+        std::mem::take(&mut self.commands)
+    }
+}
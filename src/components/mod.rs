@@ -0,0 +1,134 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// Implemented for every Clone type
+pub trait Component: Any {
+    fn clone_box(&self) -> Box<dyn Component>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Any + Clone> Component for T {
+    fn clone_box(&self) -> Box<dyn Component> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// Typed user data on a SimObject, at most one value per type
+#[derive(Default)]
+pub struct Components {
+    values: HashMap<TypeId, Box<dyn Component>>,
+}
+
+impl Clone for Components {
+    fn clone(&self) -> Self {
+        // Deref to the trait object, otherwise the blanket impl is picked for the reference itself
+        let values = self.values.iter().map(|(type_id, value)| (*type_id, (**value).clone_box())).collect();
+        Components { values }
+    }
+}
+
+impl Components {
+    pub fn new() -> Components {
+        Components::default()
+    }
+
+    /// Returns the previous value of the same type
+    pub fn insert<T: Any + Clone>(&mut self, value: T) -> Option<T> {
+        self.values.insert(TypeId::of::<T>(), Box::new(value)).and_then(|old| old.into_any().downcast().ok()).map(|old| *old)
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.values.remove(&TypeId::of::<T>()).and_then(|old| old.into_any().downcast().ok()).map(|old| *old)
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.values.get(&TypeId::of::<T>()).and_then(|value| (**value).as_any().downcast_ref())
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.values.get_mut(&TypeId::of::<T>()).and_then(|value| (**value).as_any_mut().downcast_mut())
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use physics::Physics;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Health(u32);
+
+    fn hurt(object: &mut SimObject, _context: &mut commands::Context) {
+        if let Some(health) = object.get_component_mut::<Health>() {
+            health.0 -= 1;
+        }
+    }
+
+    fn health(simulation: &Simulation, name: &str) -> u32 {
+        simulation.objects.get(simulation.find_by_name(name).unwrap()).unwrap().get_component::<Health>().unwrap().0
+    }
+
+    fn target() -> SimObject {
+        SimObject::new(0.0, 0.0, 0.0, None, None, None, Some(hurt)).with_name("target").with_component(Health(10))
+    }
+
+    #[test]
+    fn restore_rewinds_components() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::ZERO)).unwrap();
+        simulation.add_object(target()).unwrap();
+        simulation.step_once();
+        let snapshot = simulation.snapshot();
+
+        simulation.step_once();
+        simulation.step_once();
+        assert_eq!(health(&simulation, "target"), 7);
+
+        simulation.restore(&snapshot).unwrap();
+        assert_eq!(health(&simulation, "target"), 9);
+    }
+
+    #[test]
+    fn spawn_keeps_components() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::ZERO)).unwrap();
+        simulation.spawn(target()).unwrap();
+        simulation.step_once();
+        assert_eq!(health(&simulation, "target"), 9);
+    }
+
+    #[test]
+    fn snapshot_from_disk_keeps_live_components() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::ZERO)).unwrap();
+        simulation.add_object(target()).unwrap();
+        let loaded = snapshot::round_trip(&simulation.snapshot());
+
+        simulation.step_once();
+        simulation.restore(&loaded).unwrap();
+        assert_eq!(health(&simulation, "target"), 9);
+    }
+}
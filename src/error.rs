@@ -0,0 +1,79 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidTimeStep(i32),
+    InvalidTimeScale(f64),
+    InvalidMass(f64),
+    NoRenderObject(u128),
+    StaleHandle,
+    // Another registered object already has the id
+    DuplicateId(u128),
+    ParentCycle,
+    // Non-finite position, rotation or velocity
+    NonFinite,
+    // Id of the parent that isn't registered
+    MissingParent(u128),
+    // Every problem found by `Simulation::validate`, paired with the object id
+    InvalidObjects(Vec<(u128, Error)>),
+    // Recorder channels can't change once samples exist
+    RecordingStarted,
+    // Live inputs are rejected while a replay feeds the recorded ones
+    ReplayRunning,
+    Io(io::Error),
+    // Text that couldn't be read back, e.g. a scene file or input log
+    Parse(String),
+    Serialize(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidTimeStep(time_step) => write!(f, "Time step must be greater than 0, got {}", time_step),
+            Error::InvalidTimeScale(time_scale) => write!(f, "Time scale must be finite and greater than 0, got {}", time_scale),
+            Error::InvalidMass(mass) => write!(f, "Mass must be greater than 0, got {}", mass),
+            Error::NoRenderObject(id) => write!(f, "Render object is None. Object ID: {}", id),
+            Error::StaleHandle => write!(f, "Handle refers to a removed object"),
+            Error::DuplicateId(id) => write!(f, "An object with ID {} is already registered", id),
+            Error::ParentCycle => write!(f, "Parent is the child or one of its descendants"),
+            Error::NonFinite => write!(f, "Position, rotation or velocity is not finite"),
+            Error::MissingParent(parent) => write!(f, "Parent {} doesn't exist", parent),
+            Error::InvalidObjects(errors) => {
+                write!(f, "{} invalid objects", errors.len())?;
+                for (id, error) in errors {
+                    write!(f, "\n  {}: {}", id, error)?;
+                }
+                Ok(())
+            },
+            Error::RecordingStarted => write!(f, "Channels can't be added after recording has started"),
+            Error::ReplayRunning => write!(f, "Inputs can't be added while a replay is running"),
+            Error::Io(error) => write!(f, "IO error: {}", error),
+            Error::Parse(error) => write!(f, "Parse error: {}", error),
+            Error::Serialize(error) => write!(f, "Serialize error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use physics::Physics;
+
+    #[test]
+    fn invalid_time_scale_reports_the_value() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::ZERO)).unwrap();
+        let error = simulation.set_time_scale(-0.5).unwrap_err();
+        assert!(matches!(error, Error::InvalidTimeScale(time_scale) if time_scale == -0.5));
+        assert_eq!(error.to_string(), "Time scale must be finite and greater than 0, got -0.5");
+        assert_eq!(simulation.get_time_scale(), 1.0);
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use crate::*;
+use physics::Contact;
+
+/// Elastic collisions between the molecules of a container, scaled by the lower restitution of each pair
+/// `molecules` holds (object index, radius) of every molecule in the container
+pub(super) fn resolve_circle_collisions(objects: &mut [SimObject], molecules: &[(usize, f64)]) -> Vec<Contact> {
+    // (object index, position, radius)
+    let mut circles: Vec<(usize, Vector, f64)> = molecules.iter().map(|&(i, radius)| (i, objects[i].transform.position, radius)).collect();
+    let max_radius = circles.iter().map(|circle| circle.2).fold(0.0, f64::max);
+    if circles.len() < 2 || max_radius <= 0.0 { return Vec::new(); }
+
+    // Cells as wide as the largest circle, so every overlap is found in the 3x3 block around a circle
+    let cell_size = max_radius * 2.0;
+    let cell = |position: Vector| ((position.x / cell_size).floor() as i32, (position.y / cell_size).floor() as i32);
+    let mut grid: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+    for (slot, circle) in circles.iter().enumerate() {
+        grid.entry(cell(circle.1)).or_default().push(slot);
+    }
+
+    let mut contacts = Vec::new();
+    for slot_a in 0..circles.len() {
+        let (cx, cy) = cell(circles[slot_a].1);
+        for x in cx - 1..=cx + 1 {
+            for y in cy - 1..=cy + 1 {
+                let cell_slots = match grid.get(&(x, y)) {
+                    Some(cell_slots) => cell_slots,
+                    None => continue,
+                };
+                for &slot_b in cell_slots {
+                    // Each pair once
+                    if slot_b <= slot_a { continue; }
+                    if let Some(contact) = resolve_pair(objects, circles[slot_a], circles[slot_b]) {
+                        circles[slot_a].1 = objects[circles[slot_a].0].transform.position;
+                        circles[slot_b].1 = objects[circles[slot_b].0].transform.position;
+                        contacts.push(contact);
+                    }
+                }
+            }
+        }
+    }
+    contacts
+}
+
+fn resolve_pair(objects: &mut [SimObject], a: (usize, Vector, f64), b: (usize, Vector, f64)) -> Option<Contact> {
+    let offset = b.1 - a.1;
+    let distance = offset.get_mag();
+    let depth = a.2 + b.2 - distance;
+    if depth <= 0.0 || distance == 0.0 { return None; }
+
+    let phys_a = objects[a.0].physics_object.as_ref().unwrap();
+    let phys_b = objects[b.0].physics_object.as_ref().unwrap();
+    if phys_a.layer & phys_b.layer == 0 { return None; }
+
+    // Parented objects are kinematic and act as infinitely heavy
+    let inv_mass = |object: &SimObject, mass: f64| if object.parent.is_some() { 0.0 } else { 1.0 / mass };
+    let (inv_mass_a, inv_mass_b) = (inv_mass(&objects[a.0], phys_a.get_mass()), inv_mass(&objects[b.0], phys_b.get_mass()));
+    if inv_mass_a + inv_mass_b == 0.0 { return None; }
+
+    let normal = offset / distance;
+    let restitution = phys_a.restitution.min(phys_b.restitution);
+    let relative_velocity = phys_b.get_velocity() - phys_a.get_velocity();
+    let normal_speed = relative_velocity.dot(normal);
+
+    // j = -(1 + e) * vn / (1/ma + 1/mb), only when the circles are moving together
+    let mut impulse = Vector::new(0.0, 0.0);
+    if normal_speed < 0.0 {
+        impulse = normal * (-(1.0 + restitution) * normal_speed / (inv_mass_a + inv_mass_b));
+    }
+
+    // Push the circles apart in proportion to their inverse mass
+    let correction = normal * (depth / (inv_mass_a + inv_mass_b));
+    let contact = Contact { a: objects[a.0].id, b: objects[b.0].id, normal, depth };
+
+    let object_a = &mut objects[a.0];
+    object_a.transform.position -= correction * inv_mass_a;
+    let phys_a = object_a.physics_object.as_mut().unwrap();
+    phys_a.set_velocity(phys_a.get_velocity() - impulse * inv_mass_a);
+
+    let object_b = &mut objects[b.0];
+    object_b.transform.position += correction * inv_mass_b;
+    let phys_b = object_b.physics_object.as_mut().unwrap();
+    phys_b.set_velocity(phys_b.get_velocity() + impulse * inv_mass_b);
+
+    Some(contact)
+}
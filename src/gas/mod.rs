@@ -0,0 +1,369 @@
+mod collision;
+
+use serde::{Deserialize, Serialize};
+use std::time;
+
+use crate::*;
+use physics::{ColliderType, Contact, PhysicsObject};
+use rng::Rng;
+
+/// Layer bit `fill` gives its molecules, so other circles don't count as gas by default
+pub const GAS_LAYER: u32 = 1 << 31;
+
+/// Closed box of elastic circles with a movable piston as its top wall.
+/// The container collides its molecules with each other and draws them itself
+pub struct GasContainer {
+    // The piston height replaces max.y
+    pub min: Vector,
+    pub max: Vector,
+    pub piston: Piston,
+    pub thermal_mode: ThermalMode,
+    // k_B in <KE> = k_B * T, 2D molecules have two degrees of freedom
+    pub boltzmann: f64,
+    // Only circles inside the box sharing a layer bit with this mask count as molecules
+    pub layers: u32,
+    // Seconds of wall impulse averaged into one pressure reading
+    pub sample_window: f64,
+    wall_impulse: f64,
+    window_time: f64,
+    pressure: f64,
+    temperature: f64,
+    molecule_count: usize,
+    mean_mass: f64,
+    speeds: Vec<f64>,
+    // (position, radius) from the last step, for rendering
+    molecules: Vec<(Vector, f64)>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Piston {
+    pub height: f64,
+    // Positive moves up, expanding the gas
+    pub velocity: f64,
+    // The piston stops here instead of crushing the molecules
+    pub min_height: f64,
+}
+
+// Pressure is averaged over a window, so the partial window is part of the state
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GasContainerState {
+    piston: Piston,
+    wall_impulse: f64,
+    window_time: f64,
+    pressure: f64,
+}
+
+pub enum ThermalMode {
+    // Walls are perfect reflectors, a moving piston heats or cools the gas
+    Adiabatic,
+    // Velocities are rescaled every step to hold the temperature
+    Isothermal {
+        temperature: f64,
+    },
+}
+
+pub struct HistogramBin {
+    pub speed: f64,
+    // Probability density, comparable with expected
+    pub observed: f64,
+    // 2D Maxwell-Boltzmann at the measured temperature
+    pub expected: f64,
+}
+
+impl GasContainer {
+    pub fn new(min: Vector, max: Vector) -> GasContainer {
+        GasContainer {
+            min,
+            max,
+            piston: Piston {
+                height: max.y,
+                velocity: 0.0,
+                min_height: min.y + (max.y - min.y) * 0.1,
+            },
+            thermal_mode: ThermalMode::Adiabatic,
+            boltzmann: 1.0,
+            layers: GAS_LAYER,
+            sample_window: 0.5,
+            wall_impulse: 0.0,
+            window_time: 0.0,
+            pressure: 0.0,
+            temperature: 0.0,
+            molecule_count: 0,
+            mean_mass: 0.0,
+            speeds: Vec::new(),
+            molecules: Vec::new(),
+        }
+    }
+
+    /// Molecules on a jittered grid with Maxwell-Boltzmann velocities at the given temperature
+    pub fn fill(&self, count: usize, radius: f64, mass: f64, temperature: f64, seed: u64) -> Result<Vec<SimObject>, Error> {
+        let mut rng = Rng::new(seed);
+        let width = self.max.x - self.min.x - radius * 2.0;
+        let height = self.piston.height - self.min.y - radius * 2.0;
+        let columns = ((count as f64 * width / height.max(1.0)).sqrt().ceil() as usize).max(1);
+        let rows = count.div_ceil(columns);
+
+        // Each velocity component is normal with variance kT / m
+        let sigma = (self.boltzmann * temperature / mass).sqrt();
+
+        (0..count).map(|i| {
+            let x = self.min.x + radius + (i % columns) as f64 / columns as f64 * width + rng.range(0.0, radius);
+            let y = self.min.y + radius + (i / columns) as f64 / rows as f64 * height + rng.range(0.0, radius);
+            let velocity = Vector::new(rng.normal() * sigma, rng.normal() * sigma);
+
+            let mut phys_obj = PhysicsObject::new(ColliderType::Circle { radius }, mass, velocity, Vector::new(0.0, 0.0))?;
+            phys_obj.layer = self.layers;
+            Ok(SimObject::new(
+                x,
+                y,
+                0.0,
+                None,
+                Some(phys_obj),
+                None,
+                None,
+            ))
+        }).collect()
+    }
+
+    pub fn save_state(&self) -> GasContainerState {
+        GasContainerState {
+            piston: self.piston.clone(),
+            wall_impulse: self.wall_impulse,
+            window_time: self.window_time,
+            pressure: self.pressure,
+        }
+    }
+
+    pub fn restore_state(&mut self, state: &GasContainerState) {
+        self.piston = state.piston.clone();
+        self.wall_impulse = state.wall_impulse;
+        self.window_time = state.window_time;
+        self.pressure = state.pressure;
+    }
+
+    pub fn get_temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    /// Force per unit wall length, averaged over the last sample window
+    pub fn get_pressure(&self) -> f64 {
+        self.pressure
+    }
+
+    pub fn get_area(&self) -> f64 {
+        (self.max.x - self.min.x) * (self.piston.height - self.min.y)
+    }
+
+    /// P = NkT / A, for comparing against the measured pressure
+    pub fn get_ideal_pressure(&self) -> f64 {
+        self.molecule_count as f64 * self.boltzmann * self.temperature / self.get_area()
+    }
+
+    /// Returns the molecule-molecule contacts of this step
+    pub fn update(&mut self, objects: &mut [SimObject], fixed_delta_time: time::Duration) -> Vec<Contact> {
+        let dt = fixed_delta_time.as_secs_f64();
+
+        self.piston.height = (self.piston.height + self.piston.velocity * dt).clamp(self.piston.min_height, self.max.y);
+        if self.piston.height == self.piston.min_height || self.piston.height == self.max.y {
+            self.piston.velocity = 0.0;
+        }
+
+        let molecules = self.find_molecules(objects);
+        let contacts = collision::resolve_circle_collisions(objects, &molecules);
+
+        let mut kinetic_energy = 0.0;
+        let mut total_mass = 0.0;
+        self.speeds.clear();
+        self.molecules.clear();
+
+        for &(i, radius) in &molecules {
+            let object = &mut objects[i];
+            let phys_obj = object.physics_object.as_mut().unwrap();
+            let mass = phys_obj.get_mass();
+            let mut velocity = phys_obj.get_velocity();
+
+            // Reflect off the fixed walls, p = 2mv per bounce
+            if object.transform.position.x - radius < self.min.x && velocity.x < 0.0 {
+                object.transform.position.x = self.min.x + radius;
+                self.wall_impulse += 2.0 * mass * velocity.x.abs();
+                velocity.x = -velocity.x;
+            }
+            if object.transform.position.x + radius > self.max.x && velocity.x > 0.0 {
+                object.transform.position.x = self.max.x - radius;
+                self.wall_impulse += 2.0 * mass * velocity.x.abs();
+                velocity.x = -velocity.x;
+            }
+            if object.transform.position.y - radius < self.min.y && velocity.y < 0.0 {
+                object.transform.position.y = self.min.y + radius;
+                self.wall_impulse += 2.0 * mass * velocity.y.abs();
+                velocity.y = -velocity.y;
+            }
+
+            // The piston reflects in its own frame, v' = 2u - v
+            if object.transform.position.y + radius > self.piston.height && velocity.y > self.piston.velocity {
+                object.transform.position.y = self.piston.height - radius;
+                let reflected = 2.0 * self.piston.velocity - velocity.y;
+                self.wall_impulse += mass * (velocity.y - reflected).abs();
+                velocity.y = reflected;
+            }
+
+            phys_obj.set_velocity(velocity);
+            kinetic_energy += 0.5 * mass * velocity.get_mag_squared();
+            total_mass += mass;
+            self.speeds.push(velocity.get_mag());
+            self.molecules.push((object.transform.position, radius));
+        }
+
+        self.molecule_count = self.speeds.len();
+        if self.molecule_count == 0 { return contacts; }
+        self.mean_mass = total_mass / self.molecule_count as f64;
+        self.temperature = kinetic_energy / (self.molecule_count as f64 * self.boltzmann);
+
+        if let ThermalMode::Isothermal { temperature } = self.thermal_mode {
+            if self.temperature > 0.0 {
+                self.apply_thermostat(objects, &molecules, (temperature / self.temperature).sqrt());
+                self.temperature = temperature;
+            }
+        }
+
+        self.window_time += dt;
+        if self.window_time >= self.sample_window {
+            let perimeter = 2.0 * ((self.max.x - self.min.x) + (self.piston.height - self.min.y));
+            self.pressure = self.wall_impulse / (self.window_time * perimeter);
+            self.wall_impulse = 0.0;
+            self.window_time = 0.0;
+        }
+        contacts
+    }
+
+    /// (object index, radius) of the circles on the gas layers whose center is inside the box
+    fn find_molecules(&self, objects: &[SimObject]) -> Vec<(usize, f64)> {
+        objects.iter().enumerate().filter_map(|(i, object)| {
+            let phys_obj = object.physics_object.as_ref()?;
+            if phys_obj.layer & self.layers == 0 { return None; }
+            let position = object.transform.position;
+            if position.x < self.min.x || position.x > self.max.x || position.y < self.min.y || position.y > self.max.y { return None; }
+            match *phys_obj.get_collider() {
+                ColliderType::Circle { radius } => Some((i, radius)),
+                _ => None,
+            }
+        }).collect()
+    }
+
+    fn apply_thermostat(&mut self, objects: &mut [SimObject], molecules: &[(usize, f64)], scale: f64) {
+        for &(i, _) in molecules {
+            let phys_obj = objects[i].physics_object.as_mut().unwrap();
+            let velocity = phys_obj.get_velocity();
+            phys_obj.set_velocity(velocity * scale);
+        }
+        for speed in &mut self.speeds {
+            *speed *= scale;
+        }
+    }
+
+    pub fn speed_histogram(&self, bins: usize, max_speed: f64) -> Vec<HistogramBin> {
+        let bins = bins.max(1);
+        let bin_width = max_speed / bins as f64;
+        let mut counts = vec![0; bins];
+        for speed in &self.speeds {
+            let bin = (speed / bin_width) as usize;
+            if bin < bins { counts[bin] += 1; }
+        }
+
+        // f(v) = (m v / kT) * exp(-m v^2 / 2kT)
+        let kt = self.boltzmann * self.temperature;
+        let total = self.speeds.len().max(1) as f64;
+        counts.iter().enumerate().map(|(i, count)| {
+            let speed = (i as f64 + 0.5) * bin_width;
+            let expected = if kt > 0.0 {
+                self.mean_mass * speed / kt * (-self.mean_mass * speed.powi(2) / (2.0 * kt)).exp()
+            } else {
+                0.0
+            };
+            HistogramBin {
+                speed,
+                observed: *count as f64 / (total * bin_width),
+                expected,
+            }
+        }).collect()
+    }
+}
+
+impl graphics::Render for GasContainer {
+    fn render(&self, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
+        let height = canvas.output_size().unwrap().1 as i32;
+        // Invert the y axis to match the physics
+        let to_canvas = |x: f64, y: f64| sdl2::rect::Point::new(x as i32, height - y as i32);
+
+        canvas.set_draw_color(sdl2::pixels::Color::RGB(200, 200, 200));
+        let corners = [
+            to_canvas(self.min.x, self.max.y),
+            to_canvas(self.min.x, self.min.y),
+            to_canvas(self.max.x, self.min.y),
+            to_canvas(self.max.x, self.max.y),
+        ];
+        canvas.draw_lines(&corners[..]).unwrap();
+
+        canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 200, 0));
+        canvas.draw_line(to_canvas(self.min.x, self.piston.height), to_canvas(self.max.x, self.piston.height)).unwrap();
+
+        // Molecule outlines, roughly one segment per pixel of radius
+        canvas.set_draw_color(sdl2::pixels::Color::RGB(120, 200, 255));
+        for (position, radius) in &self.molecules {
+            let segments = (*radius as i32).clamp(8, 64);
+            let outline: Vec<sdl2::rect::Point> = (0..=segments).map(|i| {
+                let angle = i as f64 / segments as f64 * std::f64::consts::TAU;
+                to_canvas(position.x + angle.cos() * radius, position.y + angle.sin() * radius)
+            }).collect();
+            canvas.draw_lines(&outline[..]).unwrap();
+        }
+
+        // Live speed histogram in the top left corner, bars are measured and dots are Maxwell-Boltzmann
+        let max_speed = 3.0 * (self.boltzmann * self.temperature / self.mean_mass.max(f64::EPSILON)).sqrt();
+        if max_speed <= 0.0 { return; }
+        let histogram = self.speed_histogram(30, max_speed);
+        let peak = histogram.iter().map(|bin| bin.observed.max(bin.expected)).fold(0.0, f64::max).max(f64::EPSILON);
+        let (left, bottom, bar_width, chart_height) = (10, 110, 6, 100.0);
+
+        for (i, bin) in histogram.iter().enumerate() {
+            let x = left + i as i32 * bar_width;
+            let bar_height = (bin.observed / peak * chart_height) as i32;
+            canvas.set_draw_color(sdl2::pixels::Color::RGB(120, 200, 255));
+            if bar_height > 0 {
+                canvas.fill_rect(sdl2::rect::Rect::new(x, bottom - bar_height, bar_width as u32 - 1, bar_height as u32)).unwrap();
+            }
+            canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 80, 80));
+            canvas.fill_rect(sdl2::rect::Rect::new(x + bar_width / 2 - 1, bottom - (bin.expected / peak * chart_height) as i32 - 1, 3, 3)).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use physics::Physics;
+
+    fn ball(x: f64, y: f64, layer: u32) -> SimObject {
+        let mut phys_obj = PhysicsObject::new(ColliderType::Circle { radius: 5.0 }, 1.0, Vector::new(20.0, 0.0), Vector::ZERO).unwrap();
+        phys_obj.layer = layer;
+        SimObject::new(x, y, 0.0, None, Some(phys_obj), None, None)
+    }
+
+    #[test]
+    fn circles_outside_the_box_are_left_alone() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::ZERO)).unwrap();
+        let container = GasContainer::new(Vector::ZERO, Vector::new(100.0, 100.0));
+        for molecule in container.fill(10, 1.0, 1.0, 1.0, 7).unwrap() {
+            simulation.add_object(molecule).unwrap();
+        }
+        simulation.gas_containers.push(container);
+        let default_layer = simulation.add_object(ball(1000.0, 50.0, 1)).unwrap();
+        let gas_layer = simulation.add_object(ball(1000.0, 150.0, GAS_LAYER)).unwrap();
+
+        simulation.step_once();
+        let position = |handle| simulation.objects.get(handle).unwrap().transform.position;
+        assert!((position(default_layer).x - 1000.2).abs() < 1e-9, "{}", position(default_layer).x);
+        assert!((position(gas_layer).x - 1000.2).abs() < 1e-9, "{}", position(gas_layer).x);
+        assert_eq!(simulation.gas_containers[0].molecule_count, 10);
+    }
+}
@@ -3,6 +3,11 @@ use std::time;
 
 use crate::*;
 
+/// Anything drawn on top of the objects each frame
+pub trait Render {
+    fn render(&self, canvas: &mut Canvas<sdl2::video::Window>);
+}
+
 pub struct Graphics {
     pub sdl_context: sdl2::Sdl,
     pub video_subsystem: sdl2::VideoSubsystem,
@@ -41,7 +46,8 @@ impl Graphics {
         }
     }
 
-    pub fn update(&mut self, sim_objects: &mut Vec<SimObject>) {
+    pub fn update(&mut self, simulation: &Simulation) {
+        let sim_objects = &simulation.objects;
         let mut event_pump = self.sdl_context.event_pump().unwrap();
 
         for event in event_pump.poll_iter() {
@@ -57,16 +63,17 @@ impl Graphics {
         self.canvas.clear();
 
         // render the shapes
-        for sim_object in sim_objects.iter_mut() {
+        for sim_object in sim_objects.iter_render_order() {
             match sim_object.get_render_object() {
                 Ok(render_object) => {
+                    let transform = sim_objects.get_world_transform(sim_object);
                     //println!("Rendering object: {:?}", sim_object.id);
                     render_object.render_type.render(
                         &mut self.canvas,
                         render_object.color, 
-                        sim_object.rotation, 
+                        transform.rotation.degrees, 
                         false, 
-                        (sim_object.x, sim_object.y),
+                        (transform.position.x, transform.position.y),
                     );
                 }
                 Err(error) => {
@@ -76,6 +83,15 @@ impl Graphics {
             }
         }
 
+        let layers = simulation.soft_bodies.iter().map(|body| body as &dyn Render)
+            .chain(simulation.verlet_bodies.iter().map(|body| body as &dyn Render))
+            .chain(simulation.fluids.iter().map(|fluid| fluid as &dyn Render))
+            .chain(simulation.gas_containers.iter().map(|container| container as &dyn Render))
+            .chain(std::iter::once(&simulation.particles as &dyn Render));
+        for layer in layers {
+            layer.render(&mut self.canvas);
+        }
+
         self.canvas.present();
 
         // add the frame time until it reaches 1 second, then print the fps
@@ -114,6 +130,8 @@ impl Camera {
 pub struct RenderObject {
     pub color: sdl2::pixels::Color,
     pub render_type: RenderType,
+    // Higher is drawn on top
+    pub z_index: i32,
 }
 
 impl RenderObject {
@@ -121,6 +139,7 @@ impl RenderObject {
         RenderObject {
             color: color,
             render_type: render_type,
+            z_index: 0,
         }
     }
 }
@@ -151,7 +170,7 @@ pub enum RenderType {
 }
 
 impl RenderType {
-    fn render(&self, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, color: sdl2::pixels::Color, rotation: f64, filled: bool, coords: (f64, f64)) {
+    pub(crate) fn render(&self, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, color: sdl2::pixels::Color, rotation: f64, filled: bool, coords: (f64, f64)) {
         // Calculate camera coordinates from world coords
         // Invert the y axis to match the physics
 
@@ -198,47 +217,24 @@ impl RenderType {
                 }
 
                 // calculate center of polygon
-                let mut center: (u32, u32) = (0, 0);
-                for point in points.iter() {
-                    
-                    center.0 += point.0;
-                    center.1 += point.1;
-                }
-                center.0 /= points.len() as u32;
-                center.1 /= points.len() as u32;
+                let center = polygon_center(points);
+
+                // calculate the points of the polygon, in i32 so polygons partly off screen don't underflow
+                let height = canvas.output_size().unwrap().1 as i32;
+                let offset = (coords.0 as i32 - center.0 as i32, coords.1 as i32 - center.1 as i32);
+                let render_points: Vec<(i32, i32)> = points.iter().map(|point| (point.0 as i32 + offset.0, height - (point.1 as i32 + offset.1))).collect();
 
                 // render center dot
                 canvas.set_draw_color(color);
-                canvas.draw_point((center.0 as i32, center.1 as i32)).unwrap();
-
-                // calculate the points of the polygon
-                let offset = (coords.0 as u32 - center.0, coords.1 as u32 - center.1);
-                let mut render_points: Vec<(u32, u32)> = Vec::new();
-                for i in 0..points.len() {
-                    let mut point = points[i];
-                    point.0 += offset.0;
-                    point.1 += offset.1;
-                    point.1 = invert_canvas_y(point.1, canvas);
-                    render_points.push(point);
-                }
+                canvas.draw_point((coords.0 as i32, height - coords.1 as i32)).unwrap();
 
                 // draw the polygon
-                canvas.set_draw_color(color);
+                if filled {
+                    fill_polygon(canvas, &render_points);
+                }
                 for i in 0..render_points.len() {
-                    let point = render_points[i];
-                    let next_point = render_points[(i + 1) % render_points.len()];
-                    canvas.draw_line(
-                        (point.0 as i32, point.1 as i32),
-                        (next_point.0 as i32, next_point.1 as i32)
-                    ).unwrap();
+                    canvas.draw_line(render_points[i], render_points[(i + 1) % render_points.len()]).unwrap();
                 }
-
-                let last_point = render_points[render_points.len() - 1];
-                let first_point = render_points[0];
-                canvas.draw_line(
-                    (first_point.0 as i32, first_point.1 as i32),
-                    (last_point.0 as i32, last_point.1 as i32)
-                ).unwrap();
             },
             RenderType::Point => {
 
@@ -259,4 +255,42 @@ impl RenderType {
 
 fn invert_canvas_y(y: u32, canvas: &Canvas<sdl2::video::Window>) -> u32 {
     canvas.output_size().unwrap().1 as u32 - y
-}
\ No newline at end of file
+}
+
+pub(crate) fn polygon_center(points: &[(u32, u32)]) -> (u32, u32) {
+    let mut center: (u32, u32) = (0, 0);
+    for point in points.iter() {
+        center.0 += point.0;
+        center.1 += point.1;
+    }
+    center.0 /= points.len() as u32;
+    center.1 /= points.len() as u32;
+    center
+}
+
+// Scanline fill using the current draw colour, points are in canvas space
+fn fill_polygon(canvas: &mut Canvas<sdl2::video::Window>, points: &[(i32, i32)]) {
+    // Only scan the rows that are on screen
+    let height = canvas.output_size().unwrap().1 as i32;
+    let min_y = points.iter().map(|point| point.1).min().unwrap_or(0).max(0);
+    let max_y = points.iter().map(|point| point.1).max().unwrap_or(0).min(height);
+
+    for y in min_y..=max_y {
+        let scan_y = y as f64 + 0.5;
+        let mut crossings: Vec<f64> = Vec::new();
+        for i in 0..points.len() {
+            let (x0, y0) = (points[i].0 as f64, points[i].1 as f64);
+            let (x1, y1) = (points[(i + 1) % points.len()].0 as f64, points[(i + 1) % points.len()].1 as f64);
+            if (y0 <= scan_y) != (y1 <= scan_y) {
+                crossings.push(x0 + (scan_y - y0) / (y1 - y0) * (x1 - x0));
+            }
+        }
+        crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        for pair in crossings.chunks(2) {
+            if let [start, end] = pair {
+                canvas.draw_line((start.round() as i32, y), (end.round() as i32, y)).unwrap();
+            }
+        }
+    }
+}
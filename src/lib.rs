@@ -1,226 +1,641 @@
 pub mod physics;
+pub mod behaviour;
+pub mod commands;
+pub mod components;
+pub mod error;
 pub mod graphics;
-
-use std::{time};
+pub mod gas;
+pub mod math;
+pub mod particles;
+pub mod recorder;
+pub mod registry;
+pub mod replay;
+pub mod scene;
+pub mod scheduler;
+pub mod snapshot;
+pub mod softbody;
+pub mod sph;
+pub mod verlet;
+mod rng;
+
+use std::{collections::HashSet, time};
 
 use physics::{PhysicsObject, Physics};
+pub use error::Error;
+pub use math::{Aabb, Rot, Transform, Vector};
 use uuid::Uuid;
 
 pub struct Simulation {
-    pub objects: Vec<SimObject>,
+    pub objects: registry::ObjectRegistry,
     pub time_step: i32,
-    pub graphics: graphics::Graphics,
+    // None when running headless
+    pub graphics: Option<graphics::Graphics>,
     pub physics: physics::Physics,
+    pub particles: particles::ParticleSystem,
+    pub soft_bodies: Vec<softbody::SoftBody>,
+    pub verlet_bodies: Vec<verlet::VerletBody>,
+    pub fluids: Vec<sph::SphFluid>,
+    pub gas_containers: Vec<gas::GasContainer>,
+    pub recorder: Option<recorder::Recorder>,
+    pub scheduler: scheduler::Scheduler,
+    // Handles console commands passed to `command`
+    pub command_handler: Option<fn(&mut Simulation, &str)>,
+    pending_inputs: Vec<replay::Input>,
+    // add_object and remove_object calls made between steps while recording, already applied
+    recorded_edits: Vec<replay::Input>,
+    in_fixed_update: bool,
+    input_log: Option<replay::InputLog>,
+    replay: Option<replay::Replay>,
+    // Failed spawns from inputs and commands, collected until `take_errors`
+    errors: Vec<Error>,
     last_update_time: time::Instant,
     last_fixed_update_time: time::Instant,
     delta_time: time::Duration,
     fixed_delta_time: time::Duration,
+    simulation_time: time::Duration,
+    step_count: u64,
+    paused: bool,
+    time_scale: f64,
 }
 
 impl Simulation {
-    pub fn new(time_step: i32, physics: Physics) -> Simulation {
-        if time_step <= 0 { panic!("Time step must be greater than 0"); }
+    pub fn new(time_step: i32, physics: Physics) -> Result<Simulation, Error> {
+        let mut simulation = Simulation::headless(time_step, physics)?;
+        simulation.graphics = Some(graphics::Graphics::new());
+        Ok(simulation)
+    }
+
+    /// A simulation without a window, `update` only steps the objects
+    pub fn headless(time_step: i32, physics: Physics) -> Result<Simulation, Error> {
+        if time_step <= 0 { return Err(Error::InvalidTimeStep(time_step)); }
 
-        Simulation {
-            objects: Vec::new(),
-            graphics: graphics::Graphics::new(),
+        Ok(Simulation {
+            objects: registry::ObjectRegistry::new(),
+            graphics: None,
             physics: physics,
+            particles: particles::ParticleSystem::new(),
+            soft_bodies: Vec::new(),
+            verlet_bodies: Vec::new(),
+            fluids: Vec::new(),
+            gas_containers: Vec::new(),
+            recorder: None,
+            scheduler: scheduler::Scheduler::new(),
+            command_handler: None,
+            pending_inputs: Vec::new(),
+            recorded_edits: Vec::new(),
+            in_fixed_update: false,
+            input_log: None,
+            replay: None,
+            errors: Vec::new(),
             time_step: time_step,
             last_update_time: time::Instant::now(),
             last_fixed_update_time: time::Instant::now(),
             delta_time: time::Duration::from_secs(0),
             fixed_delta_time: time::Duration::from_secs(0),
+            simulation_time: time::Duration::from_secs(0),
+            step_count: 0,
+            paused: false,
+            time_scale: 1.0,
+        })
+    }
+
+    /// Fails if another object has the same id.
+    /// Objects added between steps while recording are logged, so the replay adds them too
+    pub fn add_object(&mut self, object: SimObject) -> Result<registry::Handle, Error> {
+        let handle = self.objects.add(object)?;
+        if self.input_log.is_some() && !self.in_fixed_update {
+            if let Some(object) = self.objects.get(handle) {
+                self.recorded_edits.push(replay::Input::Spawn(Box::new(snapshot::ObjectSnapshot::capture(object))));
+            }
         }
+        Ok(handle)
     }
 
-    pub fn update(&mut self) {
-        self.graphics.update(&mut self.objects);
+    /// Also drops its contacts and any verlet anchors attached to it, after running `on_destroy`.
+    /// Children are removed with it, call `detach_children` first to keep them
+    pub fn remove_object(&mut self, handle: registry::Handle) -> Option<SimObject> {
+        if self.input_log.is_some() && !self.in_fixed_update {
+            let id = self.objects.get(handle)?.id;
+            self.recorded_edits.push(replay::Input::Despawn(id));
+        }
+        self.destroy_object(handle)
+    }
+
+    fn destroy_object(&mut self, handle: registry::Handle) -> Option<SimObject> {
+        let id = self.objects.get(handle)?.id;
+        for child in self.objects.get_children(id) {
+            self.destroy_object(child);
+        }
+
+        let mut object = self.objects.remove(handle)?;
+        let mut context = commands::Context::new(self.delta_time);
+        behaviour::run(&mut object, &mut context, |behaviour, object, context| behaviour.on_destroy(object, context));
+        self.apply_commands(context.take_commands());
+
+        self.physics.remove_contacts(object.id);
+        for verlet_body in &mut self.verlet_bodies {
+            verlet_body.detach(object.id);
+        }
+        Some(object)
+    }
+
+    /// Runs `on_destroy` for objects a restore or scene load dropped.
+    /// The new state is taken as it is, so commands from these hooks are discarded
+    pub(crate) fn destroy_replaced(&mut self, objects: Vec<SimObject>) {
+        let mut context = commands::Context::new(self.delta_time);
+        for mut object in objects {
+            behaviour::run(&mut object, &mut context, |behaviour, object, context| behaviour.on_destroy(object, context));
+        }
+    }
+
+    /// Names aren't unique, the first match in insertion order is returned
+    pub fn find_by_name(&self, name: &str) -> Option<registry::Handle> {
+        self.objects.find_by_name(name)
+    }
+
+    pub fn find_with_tag(&self, tag: &str) -> Vec<registry::Handle> {
+        self.objects.find_with_tag(tag)
+    }
+
+    pub fn find_within_radius(&self, center: Vector, radius: f64) -> Vec<registry::Handle> {
+        self.objects.find_within_radius(center, radius)
+    }
+
+    pub fn find_with_physics(&self) -> Vec<registry::Handle> {
+        self.objects.find_with_physics()
+    }
+
+    /// The child's x, y and rotation become local to the parent, they are not converted.
+    /// Fails if it would make a cycle
+    pub fn set_parent(&mut self, child: registry::Handle, parent: Option<registry::Handle>) -> Result<(), Error> {
+        let child_id = self.objects.get(child).ok_or(Error::StaleHandle)?.id;
+        let parent_id = match parent {
+            Some(parent) => {
+                let parent_object = self.objects.get(parent).ok_or(Error::StaleHandle)?;
+                if parent_object.id == child_id || self.objects.is_ancestor(child_id, parent_object) {
+                    return Err(Error::ParentCycle);
+                }
+                Some(parent_object.id)
+            },
+            None => None,
+        };
+        self.objects.get_mut(child).unwrap().parent = parent_id;
+        Ok(())
+    }
+
+    /// Unparents the direct children, keeping their world transform
+    pub fn detach_children(&mut self, handle: registry::Handle) {
+        let id = match self.objects.get(handle) {
+            Some(object) => object.id,
+            None => return,
+        };
+        for child in self.objects.get_children(id) {
+            let transform = self.objects.get_world_transform(self.objects.get(child).unwrap());
+            let child = self.objects.get_mut(child).unwrap();
+            child.transform = transform;
+            child.parent = None;
+        }
+    }
+
+    /// Replaces every object, the gravity and the timestep with the contents of a scene file
+    pub fn load_scene<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<(), Error> {
+        let scene = scene::Scene::load(path)?;
+        if scene.time_step <= 0 {
+            return Err(Error::InvalidTimeStep(scene.time_step));
+        }
+
+        let removed = self.objects.replace_all(scene.build_objects()?)?;
+        self.destroy_replaced(removed);
+        self.time_step = scene.time_step;
+        self.physics.set_gravity(Vector::new(scene.gravity.0, scene.gravity.1));
+        self.physics.medium_density = scene.medium_density;
+        Ok(())
+    }
+
+    /// Scripts and textures can't be stored, everything else round-trips
+    pub fn save_scene<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), Error> {
+        scene::Scene::capture(self).save(path)
+    }
+
+    /// Copies the complete dynamic state, see `snapshot::Snapshot`
+    pub fn snapshot(&self) -> snapshot::Snapshot {
+        snapshot::Snapshot::capture(self)
+    }
+
+    /// Fails without changing anything if two objects in the snapshot share an id
+    pub fn restore(&mut self, snapshot: &snapshot::Snapshot) -> Result<(), Error> {
+        snapshot.restore(self)
+    }
+
+    /// Adds an object at the start of the next fixed step, recorded for replays
+    pub fn spawn(&mut self, object: SimObject) -> Result<(), Error> {
+        self.push_input(replay::Input::Spawn(Box::new(snapshot::ObjectSnapshot::capture(&object))))
+    }
+
+    /// Applies a force for the next fixed step, recorded for replays
+    pub fn apply_force(&mut self, id: u128, force: Vector) -> Result<(), Error> {
+        self.push_input(replay::Input::ApplyForce { id, force })
+    }
+
+    /// Runs `command_handler` at the start of the next fixed step, recorded for replays
+    pub fn command(&mut self, command: &str) -> Result<(), Error> {
+        self.push_input(replay::Input::Command(command.to_string()))
+    }
+
+    // The replay feeds the recorded inputs, live ones would be applied in a burst after it stops
+    fn push_input(&mut self, input: replay::Input) -> Result<(), Error> {
+        if self.replay.is_some() { return Err(Error::ReplayRunning); }
+
+        self.pending_inputs.push(input);
+        Ok(())
+    }
+
+    /// Logs every input from now on. Steps use exactly `time_step` so the run can be reproduced,
+    /// and new objects get sequential ids instead of UUIDs so the replay hands out the same ones
+    pub fn start_input_recording(&mut self) {
+        self.fixed_delta_time = time::Duration::from_micros(self.time_step as u64);
+        self.objects.set_sequential_ids(true);
+        self.recorded_edits.clear();
+        self.input_log = Some(replay::InputLog::new(self.snapshot(), self.time_step));
+    }
+
+    pub fn stop_input_recording(&mut self) -> Option<replay::InputLog> {
+        self.objects.set_sequential_ids(self.replay.is_some());
+        self.recorded_edits.clear();
+        self.input_log.take()
+    }
+
+    /// Restores the initial state of the log and feeds its inputs back in, tick by tick
+    pub fn start_replay(&mut self, log: replay::InputLog) -> Result<(), Error> {
+        self.restore(&log.initial)?;
+        self.objects.set_sequential_ids(true);
+        self.time_step = log.time_step;
+        self.fixed_delta_time = time::Duration::from_micros(self.time_step as u64);
+        self.pending_inputs.clear();
+        self.replay = Some(replay::Replay::new(log));
+        Ok(())
+    }
+
+    /// The finished or running replay, check `get_mismatches` for divergence
+    pub fn get_replay(&self) -> Option<&replay::Replay> {
+        self.replay.as_ref()
+    }
+
+    pub fn stop_replay(&mut self) -> Option<replay::Replay> {
+        self.objects.set_sequential_ids(self.input_log.is_some());
+        self.replay.take()
+    }
+
+    fn apply_inputs(&mut self, inputs: &[replay::Input]) {
+        for input in inputs {
+            match input {
+                replay::Input::Spawn(object) => {
+                    if let Err(error) = self.objects.add(object.build()) {
+                        self.errors.push(error);
+                    }
+                },
+                replay::Input::ApplyForce { id, force } => {
+                    if let Some(phys_obj) = self.objects.get_by_id_mut(*id).and_then(|object| object.physics_object.as_mut()) {
+                        phys_obj.apply_force(*force);
+                    }
+                },
+                replay::Input::Command(command) => {
+                    if let Some(command_handler) = self.command_handler {
+                        command_handler(self, command);
+                    }
+                },
+                replay::Input::Despawn(id) => {
+                    if let Some(handle) = self.objects.get_handle(*id) {
+                        self.destroy_object(handle);
+                    }
+                },
+            }
+        }
+    }
+
+    fn dispatch_collisions(&mut self) {
+        let mut context = commands::Context::new(self.fixed_delta_time);
+        for contact in self.physics.get_contacts() {
+            for (id, other) in [(contact.a, contact.b), (contact.b, contact.a)] {
+                if let Some(object) = self.objects.get_by_id_mut(id) {
+                    behaviour::run(object, &mut context, |behaviour, object, context| behaviour.on_collision(object, other, contact, context));
+                }
+            }
+        }
+        self.apply_commands(context.take_commands());
+    }
+
+    fn apply_commands(&mut self, commands: Vec<commands::Command>) {
+        for command in commands {
+            match command {
+                commands::Command::Spawn(object) => {
+                    if let Err(error) = self.objects.add(*object) {
+                        self.errors.push(error);
+                    }
+                },
+                commands::Command::Despawn(id) => {
+                    if let Some(handle) = self.objects.get_handle(id) {
+                        self.destroy_object(handle);
+                    }
+                },
+                commands::Command::ApplyForce { id, force } => {
+                    if let Some(phys_obj) = self.objects.get_by_id_mut(id).and_then(|object| object.physics_object.as_mut()) {
+                        phys_obj.apply_force(force);
+                    }
+                },
+                commands::Command::SetGravity(gravity) => self.physics.set_gravity(gravity),
+            }
+        }
+    }
+
+    /// Errors from inputs and callback commands that couldn't be applied since the last call,
+    /// such as a spawn with a duplicate id. A replay that hits one will also report mismatches
+    pub fn take_errors(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// Freezes physics and scripts, the window keeps rendering
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        // The paused time isn't simulated
+        self.fixed_delta_time = time::Duration::from_micros(self.time_step as u64);
+        self.last_fixed_update_time = time::Instant::now();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
 
-        // Update all objects if they have update
-        for object in &mut self.objects {
-            if let Some(update) = object.update {
-                update(object);
+    /// Advances exactly one fixed step of `time_step`, meant for use while paused
+    pub fn step_once(&mut self) {
+        self.fixed_delta_time = time::Duration::from_micros(self.time_step as u64);
+        self.fixed_update();
+        self.fixed_delta_time = time::Duration::from_micros(self.time_step as u64);
+        self.last_fixed_update_time = time::Instant::now();
+    }
+
+    /// Below 1 is slow motion, above 1 fast-forward. Steps get longer rather than more frequent
+    /// when fast-forwarding, except while recording or replaying where steps stay `time_step` long
+    pub fn set_time_scale(&mut self, time_scale: f64) -> Result<(), Error> {
+        if !(time_scale > 0.0 && time_scale.is_finite()) { return Err(Error::InvalidTimeScale(time_scale)); }
+        self.time_scale = time_scale;
+        Ok(())
+    }
+
+    /// Checks every object and reports all problems at once. Physics skips objects with an invalid mass
+    /// instead of crashing, so this is the place to find out about them
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut errors = Vec::new();
+        for object in self.objects.iter() {
+            let mut finite = object.transform.position.x.is_finite() && object.transform.position.y.is_finite() && object.transform.rotation.degrees.is_finite();
+            if let Some(ref phys_obj) = object.physics_object {
+                let mass = phys_obj.get_mass();
+                if !(mass > 0.0 && mass.is_finite()) {
+                    errors.push((object.id, Error::InvalidMass(mass)));
+                }
+                let velocity = phys_obj.get_velocity();
+                finite &= velocity.x.is_finite() && velocity.y.is_finite() && phys_obj.angular_velocity.is_finite();
+            }
+            if !finite {
+                errors.push((object.id, Error::NonFinite));
+            }
+            if let Some(parent) = object.parent {
+                if self.objects.get_handle(parent).is_none() {
+                    errors.push((object.id, Error::MissingParent(parent)));
+                } else if self.objects.is_ancestor(object.id, object) {
+                    errors.push((object.id, Error::ParentCycle));
+                }
             }
         }
 
-        if time::Instant::now().duration_since(self.last_fixed_update_time).as_micros() >= self.time_step as u128 {
-            self.fixed_update();
-            self.last_fixed_update_time = time::Instant::now();
+        if errors.is_empty() { Ok(()) } else { Err(Error::InvalidObjects(errors)) }
+    }
+
+    pub fn get_time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Total simulated time, the sum of every fixed step
+    pub fn get_simulation_time(&self) -> time::Duration {
+        self.simulation_time
+    }
+
+    pub fn get_step_count(&self) -> u64 {
+        self.step_count
+    }
+
+    pub fn update(&mut self) {
+        // Rendering carries on while paused
+        if let Some(mut graphics) = self.graphics.take() {
+            graphics.update(self);
+            self.graphics = Some(graphics);
+        }
+
+        if !self.paused {
+            // Update all objects if they have update
+            let mut context = commands::Context::new(self.delta_time.mul_f64(self.time_scale));
+            for object in self.objects.iter_mut() {
+                behaviour::start(object, &mut context);
+                if let Some(update) = object.update {
+                    update(object, &mut context);
+                }
+                behaviour::run(object, &mut context, |behaviour, object, context| behaviour.on_update(object, context));
+            }
+            self.apply_commands(context.take_commands());
+
+            let elapsed = time::Instant::now().duration_since(self.last_fixed_update_time).mul_f64(self.time_scale);
+            if elapsed.as_micros() >= self.time_step as u128 {
+                self.fixed_update();
+                self.last_fixed_update_time = time::Instant::now();
+            }
         }
-        
+
         self.delta_time = time::Instant::now().duration_since(self.last_update_time);
 
         self.last_update_time = time::Instant::now();
     }
 
     pub fn fixed_update(&mut self) {
+        // Objects added or removed from here on are part of the step, not edits to log
+        self.in_fixed_update = true;
+
+        // Edits between steps were applied when they were made, they are logged ahead of this step's inputs
+        let edits = std::mem::take(&mut self.recorded_edits);
+        let step = self.step_count + 1;
+        let inputs = match self.replay {
+            Some(ref mut replay) => replay.take_inputs(step),
+            None => std::mem::take(&mut self.pending_inputs),
+        };
+        self.apply_inputs(&inputs);
+
         //self.physics.update(&mut self.objects);
         // Update all objects if they have fixed_update
-        for object in &mut self.objects {
+        let mut context = commands::Context::new(self.fixed_delta_time);
+        for object in self.objects.iter_mut() {
+            behaviour::start(object, &mut context);
             if let Some(fixed_update) = object.fixed_update {
-                fixed_update(object); 
+                fixed_update(object, &mut context);
             }
+            behaviour::run(object, &mut context, |behaviour, object, context| behaviour.on_fixed_update(object, context));
+        }
+        self.apply_commands(context.take_commands());
+
+        // Systems work in world space, children are kinematic and snap back to their local transform after
+        let locals = self.objects.enter_world_space();
+        self.physics.update(self.objects.as_mut_slice(), self.fixed_delta_time);
+        for gas_container in &mut self.gas_containers {
+            let contacts = gas_container.update(self.objects.as_mut_slice(), self.fixed_delta_time);
+            self.physics.add_contacts(contacts);
+        }
+        self.particles.update(&self.objects, self.physics.get_gravity(), self.fixed_delta_time);
+        for soft_body in &mut self.soft_bodies {
+            soft_body.update(&self.objects, self.physics.get_gravity(), self.fixed_delta_time);
+        }
+        for verlet_body in &mut self.verlet_bodies {
+            verlet_body.update(&self.objects, self.physics.get_gravity(), self.fixed_delta_time);
         }
+        for fluid in &mut self.fluids {
+            fluid.update(&self.objects, self.physics.get_gravity(), self.fixed_delta_time);
+        }
+        // Measured once every system has moved, energy channels on the recorder need them too
+        if self.physics.is_diagnostics_enabled() || self.recorder.as_ref().is_some_and(|recorder| recorder.needs_diagnostics()) {
+            self.physics.update_diagnostics(&self.objects, &self.soft_bodies);
+        }
+        self.objects.leave_world_space(locals);
+        self.dispatch_collisions();
+
+        self.simulation_time += self.fixed_delta_time;
+        self.step_count += 1;
 
-        self.physics.update(&mut self.objects, self.fixed_delta_time);
+        let mut context = commands::Context::new(self.fixed_delta_time);
+        self.scheduler.run(self.simulation_time, self.step_count, &mut context);
+        self.apply_commands(context.take_commands());
+
+        // Children are sampled at their world position
+        if let Some(ref mut recorder) = self.recorder {
+            let locals = self.objects.enter_world_space();
+            recorder.record(self.step_count, self.simulation_time.as_secs_f64(), &self.objects, &self.physics);
+            self.objects.leave_world_space(locals);
+        }
 
-        self.fixed_delta_time = time::Instant::now().duration_since(self.last_fixed_update_time);
+        if self.input_log.is_some() || self.replay.is_some() {
+            let hash = replay::hash_state(self.step_count, &self.objects);
+            if let Some(ref mut input_log) = self.input_log {
+                input_log.record_tick(self.step_count, edits.into_iter().chain(inputs).collect(), hash);
+            }
+            if let Some(ref mut replay) = self.replay {
+                replay.verify(self.step_count, hash);
+            }
+            self.fixed_delta_time = time::Duration::from_micros(self.time_step as u64);
+        } else {
+            self.fixed_delta_time = time::Instant::now().duration_since(self.last_fixed_update_time).mul_f64(self.time_scale);
+        }
         self.last_fixed_update_time = time::Instant::now();
+        self.in_fixed_update = false;
     }
 }
 
 pub struct SimObject {
-    pub id: u128,
-    pub x: f64,
-    pub y: f64,
-    pub rotation: f64,
+    // Private so the registry's id lookup can't be invalidated, see `get_id`
+    id: u128,
+    pub name: Option<String>,
+    pub tags: HashSet<String>,
+    // Position and clockwise rotation in degrees
+    pub transform: Transform,
     pub render_object: Option<graphics::RenderObject>,
     pub physics_object: Option<physics::PhysicsObject>,
-    pub update: Option<fn(&mut SimObject)>,
-    pub fixed_update: Option<fn(&mut SimObject)>,
+    pub update: Option<Callback>,
+    pub fixed_update: Option<Callback>,
+    pub behaviours: Vec<Box<dyn behaviour::Behaviour>>,
+    pub components: components::Components,
+    // Id of the parent, the transform is relative to it when set
+    pub parent: Option<u128>,
+    // Behaviours before this index have had on_start called
+    started_behaviours: usize,
 }
 
+/// Spawning, despawning and touching other objects goes through the context
+pub type Callback = fn(&mut SimObject, &mut commands::Context);
+
 /// Set update to none if no update function is needed
 impl SimObject {
-    pub fn new(x: f64, y: f64, rotation: f64, render_object: Option<graphics::RenderObject>, physics_object: Option<physics::PhysicsObject>, update: Option<fn(&mut SimObject)>, fixed_update: Option<fn(&mut SimObject)>) -> SimObject {
+    pub fn new(x: f64, y: f64, rotation: f64, render_object: Option<graphics::RenderObject>, physics_object: Option<physics::PhysicsObject>, update: Option<Callback>, fixed_update: Option<Callback>) -> SimObject {
         SimObject {
             id: Uuid::new_v4().as_u128(),
-            x: x,
-            y: y,
-            rotation: rotation,
+            name: None,
+            tags: HashSet::new(),
+            transform: Transform::from_xy(x, y, rotation),
             render_object: render_object,
             physics_object: physics_object,
             update: update,
             fixed_update: fixed_update,
+            behaviours: Vec::new(),
+            components: components::Components::new(),
+            parent: None,
+            started_behaviours: 0,
         }
     }
 
-    pub fn get_render_object_mut(&mut self) -> Result<&mut graphics::RenderObject, &str> {
-        // check if render_object is None
-        if let Some(render_object) = &mut self.render_object {
-            Ok(render_object)
-        } else {
-            Err("Render object is None. Object ID: {self.id}")
-        }
+    pub fn get_id(&self) -> u128 {
+        self.id
     }
 
-    pub fn get_render_object(&self) -> Result<&graphics::RenderObject, &str> {
-        // check if render_object is None
-        if let Some(render_object) = &self.render_object {
-            Ok(render_object)
-        } else {
-            Err("Render object is None. Object ID: {self.id}")
-        }
+    pub fn get_position(&self) -> Vector {
+        self.transform.position
     }
-}
-
-#[derive(Clone, Copy)]
-pub struct Vector {
-    x: f64,
-    y: f64,
-}
 
-impl Vector {
-    pub fn new(x: f64, y: f64) -> Vector {
-        Vector {
-            x: x,
-            y: y,
-        }
+    /// Objects can hold any number of behaviours, they run in the order they were added
+    pub fn add_behaviour<B: behaviour::Behaviour + 'static>(&mut self, behaviour: B) {
+        self.behaviours.push(Box::new(behaviour));
     }
 
-    pub fn get_mag(&self) -> f64 {
-        (self.x.powi(2) + self.y.powi(2)).sqrt()
+    pub fn with_name(mut self, name: &str) -> SimObject {
+        self.name = Some(name.to_string());
+        self
     }
 
-    pub fn get_angle(&self) -> f64 {
-        self.y.atan2(self.x)
+    pub fn with_tag(mut self, tag: &str) -> SimObject {
+        self.tags.insert(tag.to_string());
+        self
     }
 
-    pub fn get_unit(&self) -> Vector {
-        let mag = self.get_mag();
-        Vector {
-            x: self.x / mag,
-            y: self.y / mag,
-        }
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
     }
 
-    pub fn get_normalized(&mut self) -> Vector {
-        let mag = self.get_mag();
-        Vector {
-            x: self.x / mag,
-            y: self.y / mag,
-        }
+    /// Shorthand for `components.insert`
+    pub fn with_component<T: std::any::Any + Clone>(mut self, value: T) -> SimObject {
+        self.components.insert(value);
+        self
     }
-}
-
-impl std::ops::Add for Vector {
-    type Output = Vector;
 
-    fn add(self, other: Vector) -> Vector {
-        Vector {
-            x: self.x + other.x,
-            y: self.y + other.y,
-        }
+    pub fn get_component<T: 'static>(&self) -> Option<&T> {
+        self.components.get()
     }
-}
 
-impl std::ops::Sub for Vector {
-    type Output = Vector;
-
-    fn sub(self, other: Vector) -> Vector {
-        Vector {
-            x: self.x - other.x,
-            y: self.y - other.y,
-        }
+    pub fn get_component_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.components.get_mut()
     }
-}
 
-impl std::ops::Mul<f64> for Vector {
-    type Output = Vector;
-
-    fn mul(self, scalar: f64) -> Vector {
-        Vector {
-            x: self.x * scalar,
-            y: self.y * scalar,
+    pub fn get_render_object_mut(&mut self) -> Result<&mut graphics::RenderObject, Error> {
+        // check if render_object is None
+        if let Some(render_object) = &mut self.render_object {
+            Ok(render_object)
+        } else {
+            Err(Error::NoRenderObject(self.id))
         }
     }
-}
-
-impl std::ops::Div<f64> for Vector {
-    type Output = Vector;
 
-    fn div(self, scalar: f64) -> Vector {
-        Vector {
-            x: self.x / scalar,
-            y: self.y / scalar,
+    pub fn get_render_object(&self) -> Result<&graphics::RenderObject, Error> {
+        // check if render_object is None
+        if let Some(render_object) = &self.render_object {
+            Ok(render_object)
+        } else {
+            Err(Error::NoRenderObject(self.id))
         }
     }
 }
-
-impl std::ops::AddAssign for Vector {
-    fn add_assign(&mut self, other: Vector) {
-        self.x += other.x;
-        self.y += other.y;
-    }
-}
-
-impl std::ops::SubAssign for Vector {
-    fn sub_assign(&mut self, other: Vector) {
-        self.x -= other.x;
-        self.y -= other.y;
-    }
-}
-
-impl std::ops::MulAssign<f64> for Vector {
-    fn mul_assign(&mut self, scalar: f64) {
-        self.x *= scalar;
-        self.y *= scalar;
-    }
-}
-
-impl std::ops::DivAssign<f64> for Vector {
-    fn div_assign(&mut self, scalar: f64) {
-        self.x /= scalar;
-        self.y /= scalar;
-    }
-}
\ No newline at end of file
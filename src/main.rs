@@ -26,11 +26,11 @@ fn main() {
         physics::Physics::new(
             Vector::new(0.0, -9.807)
         )
-    );
+    ).unwrap();
     let input = init_terminal_input();
 
     // vertical line at x, y 0, 0 
-    simulation.objects.push(
+    simulation.add_object(
         physics_sim::SimObject::new(
             10.0,
             50.0,
@@ -52,11 +52,11 @@ fn main() {
                 ColliderType::Circle { radius: 15.0 },
                 1.0,
                 Vector::new(0.0, 0.0),
-                Vector::new(0.0, 0.0))
+                Vector::new(0.0, 0.0)).unwrap()
             ),
             None,
-            Some(|obj: &mut physics_sim::SimObject| {
-                obj.rotation += 0.1;
+            Some(|obj: &mut physics_sim::SimObject, _context: &mut physics_sim::commands::Context| {
+                obj.transform.rotation.degrees += 0.1;
                 let render_obj_mut = obj.get_render_object_mut().unwrap();
                 match render_obj_mut.render_type {
                     RenderType::Line { magnitude } => {
@@ -66,7 +66,7 @@ fn main() {
                 }
             })
         )
-    );
+    ).unwrap();
     
     loop {
         // remove the newline character at the end of the input
@@ -85,5 +85,8 @@ fn main() {
         }
 
         simulation.update();
+        for error in simulation.take_errors() {
+            println!("Error: {}", error);
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,457 @@
+use serde::{Deserialize, Serialize};
+
+// Rotations follow the SimObject convention: degrees, clockwise, so local x is (cos, -sin) and local y is (sin, cos)
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Vector {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Vector {
+    pub const ZERO: Vector = Vector { x: 0.0, y: 0.0 };
+
+    pub fn new(x: f64, y: f64) -> Vector {
+        Vector {
+            x: x,
+            y: y,
+        }
+    }
+
+    pub fn get_mag(&self) -> f64 {
+        (self.x.powi(2) + self.y.powi(2)).sqrt()
+    }
+
+    pub fn get_mag_squared(&self) -> f64 {
+        self.x.powi(2) + self.y.powi(2)
+    }
+
+    pub fn get_angle(&self) -> f64 {
+        self.y.atan2(self.x)
+    }
+
+    pub fn get_unit(&self) -> Vector {
+        let mag = self.get_mag();
+        Vector {
+            x: self.x / mag,
+            y: self.y / mag,
+        }
+    }
+
+    /// Same as `get_unit`, but a zero vector stays zero instead of becoming NaN
+    pub fn get_normalized(&self) -> Vector {
+        let mag = self.get_mag();
+        if mag == 0.0 { return Vector::ZERO; }
+        Vector {
+            x: self.x / mag,
+            y: self.y / mag,
+        }
+    }
+
+    pub fn dot(&self, other: Vector) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// z component of the 3D cross product, positive when other is counterclockwise from self
+    pub fn cross(&self, other: Vector) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+
+    /// Rotated 90 degrees counterclockwise
+    pub fn perpendicular(&self) -> Vector {
+        Vector {
+            x: -self.y,
+            y: self.x,
+        }
+    }
+
+    /// t = 0 gives self, t = 1 gives other
+    pub fn lerp(&self, other: Vector, t: f64) -> Vector {
+        *self + (other - *self) * t
+    }
+
+    /// Clockwise by `degrees`, like SimObject rotation
+    pub fn rotate(&self, degrees: f64) -> Vector {
+        Rot::from_degrees(degrees).apply(*self)
+    }
+
+    /// Mirrors the vector off a surface with the given unit normal
+    pub fn reflect(&self, normal: Vector) -> Vector {
+        *self - normal * (2.0 * self.dot(normal))
+    }
+
+    pub fn distance(&self, other: Vector) -> f64 {
+        (other - *self).get_mag()
+    }
+}
+
+/// Rotation in degrees, clockwise
+#[derive(Clone, Copy, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rot {
+    pub degrees: f64,
+}
+
+impl Rot {
+    pub const IDENTITY: Rot = Rot { degrees: 0.0 };
+
+    pub fn from_degrees(degrees: f64) -> Rot {
+        Rot { degrees }
+    }
+
+    pub fn from_radians(radians: f64) -> Rot {
+        Rot { degrees: radians.to_degrees() }
+    }
+
+    pub fn to_radians(&self) -> f64 {
+        self.degrees.to_radians()
+    }
+
+    pub fn inverse(&self) -> Rot {
+        Rot { degrees: -self.degrees }
+    }
+
+    /// Local x axis in world space
+    pub fn x_axis(&self) -> Vector {
+        let (sin, cos) = self.to_radians().sin_cos();
+        Vector::new(cos, -sin)
+    }
+
+    /// Local y axis in world space, lines point along it
+    pub fn y_axis(&self) -> Vector {
+        let (sin, cos) = self.to_radians().sin_cos();
+        Vector::new(sin, cos)
+    }
+
+    /// Rotates a local vector into world space
+    pub fn apply(&self, vector: Vector) -> Vector {
+        self.x_axis() * vector.x + self.y_axis() * vector.y
+    }
+}
+
+/// Position and rotation, maps local points to world points
+#[derive(Clone, Copy, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub position: Vector,
+    pub rotation: Rot,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform { position: Vector::ZERO, rotation: Rot::IDENTITY };
+
+    pub fn new(position: Vector, rotation: Rot) -> Transform {
+        Transform { position, rotation }
+    }
+
+    pub fn from_xy(x: f64, y: f64, degrees: f64) -> Transform {
+        Transform { position: Vector::new(x, y), rotation: Rot::from_degrees(degrees) }
+    }
+
+    pub fn apply(&self, point: Vector) -> Vector {
+        self.position + self.rotation.apply(point)
+    }
+
+    /// World point back into local space
+    pub fn apply_inverse(&self, point: Vector) -> Vector {
+        self.rotation.inverse().apply(point - self.position)
+    }
+
+    /// The world transform of something placed at `local` inside this transform
+    pub fn compose(&self, local: Transform) -> Transform {
+        Transform {
+            position: self.apply(local.position),
+            rotation: self.rotation + local.rotation,
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        let rotation = self.rotation.inverse();
+        Transform {
+            position: rotation.apply(-self.position),
+            rotation,
+        }
+    }
+}
+
+/// Axis aligned bounding box
+#[derive(Clone, Copy, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Aabb {
+    pub min: Vector,
+    pub max: Vector,
+}
+
+impl Aabb {
+    pub fn new(min: Vector, max: Vector) -> Aabb {
+        Aabb {
+            min: Vector::new(min.x.min(max.x), min.y.min(max.y)),
+            max: Vector::new(min.x.max(max.x), min.y.max(max.y)),
+        }
+    }
+
+    pub fn from_center(center: Vector, half_size: Vector) -> Aabb {
+        Aabb::new(center - half_size, center + half_size)
+    }
+
+    /// None for an empty list
+    pub fn from_points(points: &[Vector]) -> Option<Aabb> {
+        let first = *points.first()?;
+        Some(points.iter().fold(Aabb::new(first, first), |aabb, point| aabb.expand_to(*point)))
+    }
+
+    pub fn get_center(&self) -> Vector {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn get_size(&self) -> Vector {
+        self.max - self.min
+    }
+
+    /// Edges count as inside
+    pub fn contains(&self, point: Vector) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x && point.y >= self.min.y && point.y <= self.max.y
+    }
+
+    /// Touching boxes intersect
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x && self.min.y <= other.max.y && self.max.y >= other.min.y
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Vector::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: Vector::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        }
+    }
+
+    pub fn expand_to(&self, point: Vector) -> Aabb {
+        self.union(&Aabb { min: point, max: point })
+    }
+
+    /// Grows every side by `margin`
+    pub fn inflate(&self, margin: f64) -> Aabb {
+        Aabb::new(self.min - Vector::new(margin, margin), self.max + Vector::new(margin, margin))
+    }
+}
+
+impl std::ops::Add for Vector {
+    type Output = Vector;
+
+    fn add(self, other: Vector) -> Vector {
+        Vector {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl std::ops::Sub for Vector {
+    type Output = Vector;
+
+    fn sub(self, other: Vector) -> Vector {
+        Vector {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+impl std::ops::Mul<f64> for Vector {
+    type Output = Vector;
+
+    fn mul(self, scalar: f64) -> Vector {
+        Vector {
+            x: self.x * scalar,
+            y: self.y * scalar,
+        }
+    }
+}
+
+impl std::ops::Mul<Vector> for f64 {
+    type Output = Vector;
+
+    fn mul(self, vector: Vector) -> Vector {
+        vector * self
+    }
+}
+
+impl std::ops::Div<f64> for Vector {
+    type Output = Vector;
+
+    fn div(self, scalar: f64) -> Vector {
+        Vector {
+            x: self.x / scalar,
+            y: self.y / scalar,
+        }
+    }
+}
+
+impl std::ops::Neg for Vector {
+    type Output = Vector;
+
+    fn neg(self) -> Vector {
+        Vector {
+            x: -self.x,
+            y: -self.y,
+        }
+    }
+}
+
+impl std::ops::AddAssign for Vector {
+    fn add_assign(&mut self, other: Vector) {
+        self.x += other.x;
+        self.y += other.y;
+    }
+}
+
+impl std::ops::SubAssign for Vector {
+    fn sub_assign(&mut self, other: Vector) {
+        self.x -= other.x;
+        self.y -= other.y;
+    }
+}
+
+impl std::ops::MulAssign<f64> for Vector {
+    fn mul_assign(&mut self, scalar: f64) {
+        self.x *= scalar;
+        self.y *= scalar;
+    }
+}
+
+impl std::ops::DivAssign<f64> for Vector {
+    fn div_assign(&mut self, scalar: f64) {
+        self.x /= scalar;
+        self.y /= scalar;
+    }
+}
+
+impl std::ops::Add for Rot {
+    type Output = Rot;
+
+    fn add(self, other: Rot) -> Rot {
+        Rot { degrees: self.degrees + other.degrees }
+    }
+}
+
+impl std::ops::Sub for Rot {
+    type Output = Rot;
+
+    fn sub(self, other: Rot) -> Rot {
+        Rot { degrees: self.degrees - other.degrees }
+    }
+}
+
+impl std::ops::AddAssign for Rot {
+    fn add_assign(&mut self, other: Rot) {
+        self.degrees += other.degrees;
+    }
+}
+
+impl std::ops::SubAssign for Rot {
+    fn sub_assign(&mut self, other: Rot) {
+        self.degrees -= other.degrees;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn assert_close(a: Vector, b: Vector) {
+        assert!(a.distance(b) < EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn dot_and_cross() {
+        let a = Vector::new(1.0, 2.0);
+        let b = Vector::new(3.0, -4.0);
+        assert_eq!(a.dot(b), -5.0);
+        assert_eq!(a.cross(b), -10.0);
+        assert_eq!(Vector::new(1.0, 0.0).cross(Vector::new(0.0, 1.0)), 1.0);
+    }
+
+    #[test]
+    fn perpendicular_is_counterclockwise() {
+        let a = Vector::new(2.0, 1.0);
+        assert_eq!(a.perpendicular(), Vector::new(-1.0, 2.0));
+        assert_eq!(a.dot(a.perpendicular()), 0.0);
+    }
+
+    #[test]
+    fn lerp_and_distance() {
+        let a = Vector::new(0.0, 0.0);
+        let b = Vector::new(3.0, 4.0);
+        assert_eq!(a.lerp(b, 0.5), Vector::new(1.5, 2.0));
+        assert_eq!(a.distance(b), 5.0);
+    }
+
+    #[test]
+    fn normalized_zero_stays_zero() {
+        assert_eq!(Vector::ZERO.get_normalized(), Vector::ZERO);
+        assert_close(Vector::new(0.0, -2.0).get_normalized(), Vector::new(0.0, -1.0));
+    }
+
+    #[test]
+    fn rotate_is_clockwise() {
+        assert_close(Vector::new(0.0, 1.0).rotate(90.0), Vector::new(1.0, 0.0));
+        assert_close(Vector::new(1.0, 0.0).rotate(90.0), Vector::new(0.0, -1.0));
+    }
+
+    #[test]
+    fn reflect_off_floor() {
+        let normal = Vector::new(0.0, 1.0);
+        assert_eq!(Vector::new(2.0, -3.0).reflect(normal), Vector::new(2.0, 3.0));
+    }
+
+    #[test]
+    fn negation() {
+        assert_eq!(-Vector::new(1.0, -2.0), Vector::new(-1.0, 2.0));
+        assert_eq!(2.0 * Vector::new(1.0, -2.0), Vector::new(2.0, -4.0));
+    }
+
+    #[test]
+    fn rot_axes_match_apply() {
+        let rot = Rot::from_degrees(30.0);
+        assert_close(rot.apply(Vector::new(1.0, 0.0)), rot.x_axis());
+        assert_close(rot.apply(Vector::new(0.0, 1.0)), rot.y_axis());
+        assert_close(rot.inverse().apply(rot.apply(Vector::new(2.0, 5.0))), Vector::new(2.0, 5.0));
+    }
+
+    #[test]
+    fn transform_round_trip() {
+        let transform = Transform::from_xy(10.0, -4.0, 37.0);
+        let point = Vector::new(3.0, 7.0);
+        assert_close(transform.apply_inverse(transform.apply(point)), point);
+        assert_close(transform.inverse().apply(transform.apply(point)), point);
+    }
+
+    #[test]
+    fn transform_compose() {
+        let parent = Transform::from_xy(5.0, 0.0, 90.0);
+        let child = Transform::from_xy(0.0, 2.0, 10.0);
+        let world = parent.compose(child);
+        assert_close(world.position, Vector::new(7.0, 0.0));
+        assert_eq!(world.rotation, Rot::from_degrees(100.0));
+        let point = Vector::new(1.0, 1.0);
+        assert_close(world.apply(point), parent.apply(child.apply(point)));
+    }
+
+    #[test]
+    fn aabb_queries() {
+        let aabb = Aabb::new(Vector::new(2.0, 2.0), Vector::new(0.0, 0.0));
+        assert_eq!(aabb.min, Vector::new(0.0, 0.0));
+        assert_eq!(aabb.get_center(), Vector::new(1.0, 1.0));
+        assert!(aabb.contains(Vector::new(2.0, 1.0)));
+        assert!(!aabb.contains(Vector::new(2.5, 1.0)));
+        assert!(aabb.intersects(&Aabb::from_center(Vector::new(3.0, 1.0), Vector::new(1.0, 1.0))));
+        assert!(!aabb.intersects(&Aabb::from_center(Vector::new(4.0, 1.0), Vector::new(1.0, 1.0))));
+    }
+
+    #[test]
+    fn aabb_from_points() {
+        assert!(Aabb::from_points(&[]).is_none());
+        let aabb = Aabb::from_points(&[Vector::new(1.0, -1.0), Vector::new(-2.0, 3.0), Vector::new(0.0, 0.0)]).unwrap();
+        assert_eq!(aabb, Aabb::new(Vector::new(-2.0, -1.0), Vector::new(1.0, 3.0)));
+        assert_eq!(aabb.inflate(1.0).get_size(), Vector::new(5.0, 6.0));
+    }
+}
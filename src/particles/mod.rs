@@ -0,0 +1,311 @@
+use sdl2::pixels::Color;
+use serde::{Deserialize, Serialize};
+use std::time;
+
+use crate::*;
+use rng::Rng;
+use physics::ColliderType;
+
+pub struct ParticleSystem {
+    // Kept private so particles can't be left pointing at a removed emitter
+    emitters: Vec<(EmitterId, Emitter)>,
+    particles: Vec<Particle>,
+    rng: Rng,
+    next_emitter_id: u32,
+}
+
+/// Stable reference to an emitter, stays valid when other emitters are removed
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct EmitterId(u32);
+
+impl ParticleSystem {
+    pub fn new() -> ParticleSystem {
+        ParticleSystem {
+            emitters: Vec::new(),
+            particles: Vec::new(),
+            rng: Rng::new(0x2545F4914F6CDD1D),
+            next_emitter_id: 0,
+        }
+    }
+
+    pub fn add_emitter(&mut self, emitter: Emitter) -> EmitterId {
+        let id = EmitterId(self.next_emitter_id);
+        self.next_emitter_id += 1;
+        self.emitters.push((id, emitter));
+        id
+    }
+
+    /// Particles from the emitter are removed with it
+    pub fn remove_emitter(&mut self, id: EmitterId) -> Option<Emitter> {
+        let index = self.emitters.iter().position(|(emitter_id, _)| *emitter_id == id)?;
+        self.particles.retain(|particle| particle.emitter != id);
+        Some(self.emitters.remove(index).1)
+    }
+
+    pub fn get_emitter(&self, id: EmitterId) -> Option<&Emitter> {
+        self.emitters.iter().find(|(emitter_id, _)| *emitter_id == id).map(|(_, emitter)| emitter)
+    }
+
+    pub fn get_emitter_mut(&mut self, id: EmitterId) -> Option<&mut Emitter> {
+        self.emitters.iter_mut().find(|(emitter_id, _)| *emitter_id == id).map(|(_, emitter)| emitter)
+    }
+
+    pub fn emitters(&self) -> impl Iterator<Item = (EmitterId, &Emitter)> {
+        self.emitters.iter().map(|(id, emitter)| (*id, emitter))
+    }
+
+    pub fn save_state(&self) -> ParticleSystemState {
+        ParticleSystemState {
+            particles: self.particles.clone(),
+            spawn_accumulators: self.emitters.iter().map(|(id, emitter)| (*id, emitter.spawn_accumulator)).collect(),
+            rng: self.rng.clone(),
+        }
+    }
+
+    /// Emitters are matched by id, particles from emitters that no longer exist are dropped
+    pub fn restore_state(&mut self, state: &ParticleSystemState) {
+        self.particles = state.particles.iter().filter(|particle| self.get_emitter(particle.emitter).is_some()).cloned().collect();
+        for (id, spawn_accumulator) in &state.spawn_accumulators {
+            if let Some(emitter) = self.get_emitter_mut(*id) {
+                emitter.spawn_accumulator = *spawn_accumulator;
+            }
+        }
+        self.rng = state.rng.clone();
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    pub fn update(&mut self, objects: &[SimObject], gravity: Vector, fixed_delta_time: time::Duration) {
+        let dt = fixed_delta_time.as_secs_f64();
+
+        // Spawn new particles, the fractional part carries over to the next step
+        for (id, emitter) in self.emitters.iter_mut() {
+            if !emitter.enabled { continue; }
+
+            emitter.spawn_accumulator += emitter.spawn_rate * dt;
+            while emitter.spawn_accumulator >= 1.0 {
+                emitter.spawn_accumulator -= 1.0;
+                self.particles.push(emitter.spawn(*id, &mut self.rng));
+            }
+        }
+
+        // Age and remove dead particles
+        for particle in &mut self.particles {
+            particle.age += dt;
+        }
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+
+        let emitters = &self.emitters;
+        for particle in &mut self.particles {
+            let emitter = match emitters.iter().find(|(id, _)| *id == particle.emitter) {
+                Some((_, emitter)) => emitter,
+                None => continue,
+            };
+            let old_position = particle.position;
+
+            // v = u + a * t, s = v * t
+            particle.velocity += gravity * (emitter.gravity_scale * dt);
+            particle.velocity *= 1.0 / (1.0 + emitter.damping * dt);
+            particle.position += particle.velocity * dt;
+
+            if emitter.collide {
+                for object in objects {
+                    if let Some(ref phys_obj) = object.physics_object {
+                        if phys_obj.layer & emitter.layers == 0 { continue; }
+                        collide_particle(particle, old_position, object, phys_obj.get_collider(), emitter.restitution);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl graphics::Render for ParticleSystem {
+    fn render(&self, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
+        let height = canvas.output_size().unwrap().1 as i32;
+        canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
+        for particle in &self.particles {
+            let emitter = match self.get_emitter(particle.emitter) {
+                Some(emitter) => emitter,
+                None => continue,
+            };
+            let t = particle.age / particle.lifetime;
+
+            let size = (emitter.start_size + (emitter.end_size - emitter.start_size) * t).max(1.0) as u32;
+            let x = particle.position.x as i32 - size as i32 / 2;
+            // Invert the y axis to match the physics
+            let y = height - particle.position.y as i32 - size as i32 / 2;
+
+            canvas.set_draw_color(lerp_color(emitter.start_color, emitter.end_color, t));
+            canvas.fill_rect(sdl2::rect::Rect::new(x, y, size, size)).unwrap();
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ParticleSystemState {
+    particles: Vec<Particle>,
+    spawn_accumulators: Vec<(EmitterId, f64)>,
+    rng: Rng,
+}
+
+impl Default for ParticleSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Emitter {
+    pub x: f64,
+    pub y: f64,
+    pub enabled: bool,
+    // Particles per second
+    pub spawn_rate: f64,
+    // Seconds, each particle picks a lifetime between min and max
+    pub lifetime: (f64, f64),
+    // Centre of the velocity cone in degrees, clockwise from up like SimObject::rotation
+    pub direction: f64,
+    // Full width of the velocity cone in degrees
+    pub spread: f64,
+    pub speed: (f64, f64),
+    pub start_color: Color,
+    pub end_color: Color,
+    // Pixels
+    pub start_size: f64,
+    pub end_size: f64,
+    // 1 falls like any other object, 0 ignores gravity, negative rises like smoke
+    pub gravity_scale: f64,
+    // Fraction of velocity lost per second
+    pub damping: f64,
+    // Particles bounce off colliders sharing a layer bit with this mask
+    pub collide: bool,
+    pub layers: u32,
+    pub restitution: f64,
+    spawn_accumulator: f64,
+}
+
+impl Emitter {
+    pub fn new(x: f64, y: f64, spawn_rate: f64, lifetime: (f64, f64), direction: f64, spread: f64, speed: (f64, f64)) -> Emitter {
+        Emitter {
+            x,
+            y,
+            enabled: true,
+            spawn_rate,
+            lifetime,
+            direction,
+            spread,
+            speed,
+            start_color: Color::RGB(255, 255, 255),
+            end_color: Color::RGBA(255, 255, 255, 0),
+            start_size: 2.0,
+            end_size: 2.0,
+            gravity_scale: 1.0,
+            damping: 0.0,
+            collide: false,
+            layers: u32::MAX,
+            restitution: 0.5,
+            spawn_accumulator: 0.0,
+        }
+    }
+
+    fn spawn(&self, emitter: EmitterId, rng: &mut Rng) -> Particle {
+        let angle = (self.direction + (rng.next_f64() - 0.5) * self.spread).to_radians();
+        let speed = rng.range(self.speed.0, self.speed.1);
+
+        Particle {
+            position: Vector::new(self.x, self.y),
+            velocity: Vector::new(angle.sin(), angle.cos()) * speed,
+            age: 0.0,
+            lifetime: rng.range(self.lifetime.0, self.lifetime.1),
+            emitter,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Particle {
+    position: Vector,
+    velocity: Vector,
+    age: f64,
+    lifetime: f64,
+    emitter: EmitterId,
+}
+
+fn collide_particle(particle: &mut Particle, old_position: Vector, object: &SimObject, collider: &ColliderType, restitution: f64) {
+    let center = object.transform.position;
+
+    if let Some((point, normal)) = point_contact(collider, center, object.transform.rotation.degrees, old_position, particle.position) {
+        // Reflect the normal part of the velocity
+        let normal_speed = particle.velocity.dot(normal);
+        if normal_speed < 0.0 {
+            particle.velocity -= normal * ((1.0 + restitution) * normal_speed);
+        }
+        particle.position = point + normal * 0.01;
+    }
+}
+
+/// Where a point moving from `old_point` to `point` hits the collider, with the outward surface normal.
+/// Circles and rectangles push out points that end up inside, lines catch points that cross them.
+/// Soft bodies, ropes and fluids reuse this for their point masses
+pub(crate) fn point_contact(collider: &ColliderType, position: Vector, rotation: f64, old_point: Vector, point: Vector) -> Option<(Vector, Vector)> {
+    let rotation = Rot::from_degrees(rotation);
+    let (local_x, local_y) = (rotation.x_axis(), rotation.y_axis());
+
+    // Find the surface normal if the point ended up inside or crossed the collider
+    match collider {
+        ColliderType::Circle { radius } => {
+            let offset = point - position;
+            let distance = offset.get_mag();
+            if distance >= *radius || distance == 0.0 { return None; }
+            Some((position + offset.get_unit() * *radius, offset.get_unit()))
+        },
+        ColliderType::Rectangle { width, height } => {
+            let offset = point - position;
+            let px = offset.dot(local_x);
+            let py = offset.dot(local_y);
+            let (half_w, half_h) = (width / 2.0, height / 2.0);
+            if px.abs() >= half_w || py.abs() >= half_h { return None; }
+
+            // Push out through the closest face
+            if half_w - px.abs() < half_h - py.abs() {
+                let normal = local_x * px.signum();
+                Some((position + local_x * (half_w * px.signum()) + local_y * py, normal))
+            } else {
+                let normal = local_y * py.signum();
+                Some((position + local_x * px + local_y * (half_h * py.signum()), normal))
+            }
+        },
+        ColliderType::Line { length } => {
+            segment_crossing(old_point, point, position, position + local_y * *length)
+        },
+    }
+}
+
+// Where the path a -> b crosses the segment c -> d, with the normal facing a
+fn segment_crossing(a: Vector, b: Vector, c: Vector, d: Vector) -> Option<(Vector, Vector)> {
+    let r = b - a;
+    let s = d - c;
+    let denominator = r.cross(s);
+    if denominator == 0.0 { return None; }
+
+    let t = (c - a).cross(s) / denominator;
+    let u = (c - a).cross(r) / denominator;
+    if !(0.0..=1.0).contains(&t) || !(0.0..=1.0).contains(&u) { return None; }
+
+    let mut normal = s.perpendicular().get_unit();
+    if normal.dot(r) > 0.0 {
+        normal *= -1.0;
+    }
+    Some((a + r * t, normal))
+}
+
+fn lerp_color(start: Color, end: Color, t: f64) -> Color {
+    let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+    Color::RGBA(lerp(start.r, end.r), lerp(start.g, end.g), lerp(start.b, end.b), lerp(start.a, end.a))
+}
@@ -0,0 +1,187 @@
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+use crate::*;
+use super::electro::CoulombMode;
+use super::nbody::GravityMode;
+use super::Physics;
+
+/// Energy and momentum totals for one fixed step
+#[derive(Clone, Default)]
+pub struct Diagnostics {
+    pub kinetic: f64,
+    pub rotational: f64,
+    pub gravitational_potential: f64,
+    pub electric_potential: f64,
+    pub spring_potential: f64,
+    pub linear_momentum: Vector,
+    // About the origin, counter-clockwise positive
+    pub angular_momentum: f64,
+    objects: Vec<ObjectDiagnostics>,
+    // Object id to index in objects
+    index: HashMap<u128, usize>,
+}
+
+#[derive(Clone)]
+pub struct ObjectDiagnostics {
+    pub id: u128,
+    pub kinetic: f64,
+    pub rotational: f64,
+    // Pair potentials are split evenly between the two objects
+    pub potential: f64,
+    pub linear_momentum: Vector,
+    pub angular_momentum: f64,
+}
+
+impl Diagnostics {
+    pub fn get_total_energy(&self) -> f64 {
+        self.kinetic + self.rotational + self.gravitational_potential + self.electric_potential + self.spring_potential
+    }
+
+    pub fn get_object(&self, id: u128) -> Option<&ObjectDiagnostics> {
+        self.index.get(&id).map(|index| &self.objects[*index])
+    }
+
+    pub fn get_objects(&self) -> &[ObjectDiagnostics] {
+        &self.objects
+    }
+
+    fn push_object(&mut self, object: ObjectDiagnostics) {
+        self.index.insert(object.id, self.objects.len());
+        self.objects.push(object);
+    }
+
+    /// Adds the point masses and springs of a soft body to the totals
+    pub fn add_soft_body(&mut self, soft_body: &softbody::SoftBody, gravity: Vector) {
+        for point in &soft_body.points {
+            let momentum = point.velocity * point.mass;
+            self.kinetic += 0.5 * point.mass * point.velocity.get_mag_squared();
+            self.gravitational_potential -= point.mass * gravity.dot(point.position);
+            self.linear_momentum += momentum;
+            self.angular_momentum += point.position.x * momentum.y - point.position.y * momentum.x;
+        }
+
+        // E = 1/2 k (x - x0)^2
+        for spring in &soft_body.springs {
+            let length = (soft_body.points[spring.b].position - soft_body.points[spring.a].position).get_mag();
+            self.spring_potential += 0.5 * spring.stiffness * (length - spring.rest_length).powi(2);
+        }
+    }
+}
+
+impl Physics {
+    /// Recomputes the stored diagnostics, called at the end of the step when they are enabled
+    pub(crate) fn update_diagnostics(&mut self, objects: &[SimObject], soft_bodies: &[softbody::SoftBody]) {
+        let mut diagnostics = self.calc_diagnostics(objects);
+        for soft_body in soft_bodies {
+            diagnostics.add_soft_body(soft_body, self.gravity);
+        }
+        self.diagnostics = diagnostics;
+    }
+
+    pub fn calc_diagnostics(&self, objects: &[SimObject]) -> Diagnostics {
+        let mut diagnostics = Diagnostics::default();
+
+        // (object index, position, mass, charge)
+        let bodies: Vec<(usize, Vector, f64, f64)> = objects.iter().enumerate().filter_map(|(i, object)| {
+            object.physics_object.as_ref().map(|phys_obj| (i, object.transform.position, phys_obj.mass, phys_obj.charge))
+        }).collect();
+
+        let pair_potentials = self.calc_pair_potentials(&bodies);
+
+        for (slot, &(i, position, mass, charge)) in bodies.iter().enumerate() {
+            let object = &objects[i];
+            let phys_obj = object.physics_object.as_ref().unwrap();
+
+            // Stored angular velocity is clockwise degrees, the maths wants counter-clockwise radians
+            let omega = -phys_obj.angular_velocity.to_radians();
+            let inertia = phys_obj.moment_of_inertia();
+            let velocity = phys_obj.velocity;
+            let momentum = velocity * mass;
+
+            let kinetic = 0.5 * mass * velocity.get_mag_squared();
+            let rotational = 0.5 * inertia * omega.powi(2);
+            let angular_momentum = position.cross(momentum) + inertia * omega;
+
+            // U = -m g . r in a uniform field, U = -q E . r in the uniform electric field
+            let (mut gravitational, mut electric) = pair_potentials[slot];
+            if let GravityMode::Uniform = self.gravity_mode {
+                gravitational -= mass * self.gravity.dot(position);
+            }
+            electric -= charge * self.electric_field.dot(position);
+
+            diagnostics.kinetic += kinetic;
+            diagnostics.rotational += rotational;
+            diagnostics.gravitational_potential += gravitational;
+            diagnostics.electric_potential += electric;
+            diagnostics.linear_momentum += momentum;
+            diagnostics.angular_momentum += angular_momentum;
+            diagnostics.push_object(ObjectDiagnostics {
+                id: object.id,
+                kinetic,
+                rotational,
+                potential: gravitational + electric,
+                linear_momentum: momentum,
+                angular_momentum,
+            });
+        }
+
+        diagnostics
+    }
+
+    // Half of every N-body and Coulomb pair potential for each body, (gravitational, electric)
+    fn calc_pair_potentials(&self, bodies: &[(usize, Vector, f64, f64)]) -> Vec<(f64, f64)> {
+        let gravity = match self.gravity_mode {
+            GravityMode::NBody { constant, softening, .. } => Some((constant, softening)),
+            GravityMode::Uniform => None,
+        };
+        let coulomb = match self.coulomb_mode {
+            CoulombMode::Direct { constant, softening } => Some((constant, softening)),
+            CoulombMode::None => None,
+        };
+        if gravity.is_none() && coulomb.is_none() {
+            return vec![(0.0, 0.0); bodies.len()];
+        }
+
+        bodies.par_iter().enumerate().map(|(slot, &(_, position, mass, charge))| {
+            let mut potential = (0.0, 0.0);
+            for (other_slot, &(_, other_position, other_mass, other_charge)) in bodies.iter().enumerate() {
+                if slot == other_slot { continue; }
+                let dist_sq = (other_position - position).get_mag_squared();
+
+                // U = -G m1 m2 / r and U = k q1 q2 / r, softened the same way as the forces
+                if let Some((constant, softening)) = gravity {
+                    potential.0 -= 0.5 * constant * mass * other_mass / (dist_sq + softening.powi(2)).sqrt();
+                }
+                if let Some((constant, softening)) = coulomb {
+                    if charge != 0.0 && other_charge != 0.0 {
+                        potential.1 += 0.5 * constant * charge * other_charge / (dist_sq + softening.powi(2)).sqrt();
+                    }
+                }
+            }
+            potential
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use physics::{ColliderType, Physics, PhysicsObject};
+
+    #[test]
+    fn reported_every_step_unless_disabled() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::ZERO)).unwrap();
+        let phys_obj = PhysicsObject::new(ColliderType::Circle { radius: 1.0 }, 2.0, Vector::new(3.0, 4.0), Vector::ZERO).unwrap();
+        simulation.add_object(SimObject::new(0.0, 0.0, 0.0, None, Some(phys_obj), None, None)).unwrap();
+
+        simulation.step_once();
+        let diagnostics = simulation.physics.get_diagnostics();
+        assert!((diagnostics.kinetic - 25.0).abs() < 1e-9);
+        assert!((diagnostics.linear_momentum - Vector::new(6.0, 8.0)).get_mag() < 1e-9);
+
+        simulation.physics.set_diagnostics_enabled(false);
+        simulation.step_once();
+        assert_eq!(simulation.physics.get_diagnostics().kinetic, 0.0);
+    }
+}
@@ -0,0 +1,30 @@
+use crate::*;
+use super::nbody;
+
+pub enum CoulombMode {
+    // Charges only respond to the uniform fields
+    None,
+    // Every charge pushes on every other charge, F = k * q1 * q2 / (d^2 + softening^2)
+    Direct {
+        constant: f64,
+        softening: f64,
+    },
+}
+
+pub struct Charge {
+    pub position: Vector,
+    pub charge: f64,
+}
+
+pub fn calc_coulomb_forces(charges: &[Charge], constant: f64, softening: f64) -> Vec<Vector> {
+    // Same pairwise law as gravity with the sign flipped so like charges repel.
+    // Barnes-Hut is not offered since mixed signs break the centre of mass approximation
+    let bodies: Vec<nbody::Body> = charges.iter().map(|charge| nbody::Body { position: charge.position, mass: charge.charge }).collect();
+    nbody::calc_forces(&bodies, -constant, softening, &nbody::NBodySolver::Direct)
+}
+
+/// F = q(E + v x B), B points out of the screen
+pub fn calc_lorentz_force(charge: f64, velocity: Vector, electric_field: Vector, magnetic_field: f64) -> Vector {
+    let magnetic = Vector::new(velocity.y * magnetic_field, -velocity.x * magnetic_field);
+    (electric_field + magnetic) * charge
+}
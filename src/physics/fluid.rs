@@ -0,0 +1,102 @@
+use crate::*;
+use super::ColliderType;
+
+pub struct FluidVolume {
+    // The top edge of the volume (max.y) is the fluid surface
+    pub min: Vector,
+    pub max: Vector,
+    // kg/m^2
+    pub density: f64,
+    // Drag coefficient Cd used for submerged bodies
+    pub drag: f64,
+}
+
+impl FluidVolume {
+    pub fn new(min: Vector, max: Vector, density: f64, drag: f64) -> FluidVolume {
+        FluidVolume {
+            min,
+            max,
+            density,
+            drag,
+        }
+    }
+
+    pub fn surface(&self) -> f64 {
+        self.max.y
+    }
+
+    /// Returns the submerged area and its centroid (the centre of buoyancy)
+    pub fn calc_submerged(&self, collider: &ColliderType, position: Vector, rotation: f64) -> Option<(f64, Vector)> {
+        let mut polygon = collider.to_polygon(position, rotation);
+
+        // Sutherland-Hodgman against the four sides of the volume
+        polygon = clip(&polygon, |p| p.y <= self.max.y, |a, b| lerp_at(a, b, (self.max.y - a.y) / (b.y - a.y)));
+        polygon = clip(&polygon, |p| p.y >= self.min.y, |a, b| lerp_at(a, b, (self.min.y - a.y) / (b.y - a.y)));
+        polygon = clip(&polygon, |p| p.x <= self.max.x, |a, b| lerp_at(a, b, (self.max.x - a.x) / (b.x - a.x)));
+        polygon = clip(&polygon, |p| p.x >= self.min.x, |a, b| lerp_at(a, b, (self.min.x - a.x) / (b.x - a.x)));
+
+        polygon_area_centroid(&polygon)
+    }
+
+    /// Buoyancy and fluid drag on a body, returns the force and the torque about the body's position
+    pub fn calc_force(&self, collider: &ColliderType, position: Vector, rotation: f64, velocity: Vector, gravity: Vector) -> Option<(Vector, f64)> {
+        let (area, centre_of_buoyancy) = self.calc_submerged(collider, position, rotation)?;
+
+        // Archimedes, F = -rho * V * g
+        let buoyancy = gravity * (-self.density * area);
+        let arm = centre_of_buoyancy - position;
+        let torque = arm.x * buoyancy.y - arm.y * buoyancy.x;
+
+        // Quadratic drag, scaled by how much of the body is under the surface
+        let mut drag = Vector::new(0.0, 0.0);
+        let speed = velocity.get_mag();
+        let total_area = collider.area();
+        if speed > 0.0 && total_area > 0.0 {
+            let cross_section = collider.cross_section(rotation, velocity.get_unit());
+            drag = velocity * (-0.5 * self.density * self.drag * cross_section * speed * (area / total_area));
+        }
+
+        Some((buoyancy + drag, torque))
+    }
+}
+
+fn lerp_at(a: Vector, b: Vector, t: f64) -> Vector {
+    a + (b - a) * t
+}
+
+fn clip(polygon: &[Vector], inside: impl Fn(Vector) -> bool, intersect: impl Fn(Vector, Vector) -> Vector) -> Vec<Vector> {
+    let mut output = Vec::new();
+    for i in 0..polygon.len() {
+        let current = polygon[i];
+        let previous = polygon[(i + polygon.len() - 1) % polygon.len()];
+        match (inside(previous), inside(current)) {
+            (true, true) => output.push(current),
+            (true, false) => output.push(intersect(previous, current)),
+            (false, true) => {
+                output.push(intersect(previous, current));
+                output.push(current);
+            },
+            (false, false) => {},
+        }
+    }
+    output
+}
+
+fn polygon_area_centroid(polygon: &[Vector]) -> Option<(f64, Vector)> {
+    if polygon.len() < 3 { return None; }
+
+    // Shoelace formula
+    let mut area = 0.0;
+    let mut centroid = Vector::new(0.0, 0.0);
+    for i in 0..polygon.len() {
+        let a = polygon[i];
+        let b = polygon[(i + 1) % polygon.len()];
+        let cross = a.x * b.y - b.x * a.y;
+        area += cross;
+        centroid += (a + b) * cross;
+    }
+    area /= 2.0;
+    if area.abs() < f64::EPSILON { return None; }
+
+    Some((area.abs(), centroid / (6.0 * area)))
+}
@@ -0,0 +1,97 @@
+use crate::*;
+
+/// Every field type is an acceleration field: `strength` and `acceleration` are in units/s^2
+/// and are scaled by mass, so light and heavy objects respond the same way
+pub struct ForceField {
+    pub field_type: FieldType,
+    // Bitmask, the field only acts on objects whose layer shares a bit with it
+    pub layers: u32,
+}
+
+impl ForceField {
+    pub fn new(field_type: FieldType, layers: u32) -> ForceField {
+        ForceField {
+            field_type,
+            layers,
+        }
+    }
+
+    pub fn affects(&self, layer: u32) -> bool {
+        self.layers & layer != 0
+    }
+
+    pub fn calc_force(&self, position: Vector, mass: f64) -> Vector {
+        match &self.field_type {
+            FieldType::Radial { x, y, strength, radius, falloff } => {
+                let offset = Vector::new(x - position.x, y - position.y);
+                let distance = offset.get_mag();
+                if distance == 0.0 || distance > *radius { return Vector::new(0.0, 0.0); }
+
+                // Positive strength attracts, negative repels
+                offset.get_unit() * (strength * mass * falloff.scale(distance, *radius))
+            },
+            FieldType::Wind { min, max, acceleration } => {
+                if position.x < min.x || position.x > max.x || position.y < min.y || position.y > max.y {
+                    return Vector::new(0.0, 0.0);
+                }
+
+                *acceleration * mass
+            },
+            FieldType::Vortex { x, y, strength, radius, falloff } => {
+                let offset = Vector::new(position.x - x, position.y - y);
+                let distance = offset.get_mag();
+                if distance == 0.0 || distance > *radius { return Vector::new(0.0, 0.0); }
+
+                // Tangent to the circle around the centre, positive strength spins counter-clockwise
+                let tangent = offset.perpendicular() / distance;
+                tangent * (strength * mass * falloff.scale(distance, *radius))
+            },
+        }
+    }
+}
+
+pub enum FieldType {
+    // Point attractor, use a negative strength for a repulsor
+    Radial {
+        x: f64,
+        y: f64,
+        strength: f64,
+        radius: f64,
+        falloff: Falloff,
+    },
+    // Uniform acceleration inside an axis aligned region
+    Wind {
+        min: Vector,
+        max: Vector,
+        acceleration: Vector,
+    },
+    Vortex {
+        x: f64,
+        y: f64,
+        strength: f64,
+        radius: f64,
+        falloff: Falloff,
+    },
+}
+
+#[derive(Clone, Copy)]
+pub enum Falloff {
+    Constant,
+    // Fades to 0 at the edge of the field
+    Linear,
+    // 1 / d^2, held at 1 / min_distance^2 closer in so the centre doesn't blow up.
+    // min_distance is in world units, pick it to match the scale of the scene
+    InverseSquare {
+        min_distance: f64,
+    },
+}
+
+impl Falloff {
+    fn scale(&self, distance: f64, radius: f64) -> f64 {
+        match self {
+            Falloff::Constant => 1.0,
+            Falloff::Linear => 1.0 - distance / radius,
+            Falloff::InverseSquare { min_distance } => 1.0 / distance.max(*min_distance).powi(2),
+        }
+    }
+}
@@ -1,60 +1,262 @@
+pub mod diagnostics;
+pub mod electro;
+pub mod fluid;
+pub mod force_field;
+pub mod nbody;
+
+use serde::{Deserialize, Serialize};
+
 use crate::*;
+use diagnostics::Diagnostics;
+use electro::CoulombMode;
+use fluid::FluidVolume;
+use force_field::ForceField;
+use nbody::GravityMode;
 
 pub struct Physics {
     gravity: Vector,
+    // Density of the surrounding medium (kg/m^2), 0 is a vacuum
+    pub medium_density: f64,
+    pub force_fields: Vec<ForceField>,
+    pub gravity_mode: GravityMode,
+    pub fluid_volumes: Vec<FluidVolume>,
+    pub coulomb_mode: CoulombMode,
+    // Uniform electric field (N/C)
+    pub electric_field: Vector,
+    // Uniform magnetic field (T), positive points out of the screen
+    pub magnetic_field: f64,
+    contacts: Vec<Contact>,
+    diagnostics: Diagnostics,
+    diagnostics_enabled: bool,
+}
 
+/// Reported by collision passes such as gas containers
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Contact {
+    pub a: u128,
+    pub b: u128,
+    // Points from a to b
+    pub normal: Vector,
+    pub depth: f64,
 }
 
 impl Physics {
     pub fn new(gravity: Vector) -> Physics {
         Physics {
             gravity: gravity,
+            medium_density: 0.0,
+            force_fields: Vec::new(),
+            gravity_mode: GravityMode::Uniform,
+            fluid_volumes: Vec::new(),
+            coulomb_mode: CoulombMode::None,
+            electric_field: Vector::new(0.0, 0.0),
+            magnetic_field: 0.0,
+            contacts: Vec::new(),
+            diagnostics: Diagnostics::default(),
+            diagnostics_enabled: true,
+        }
+    }
+
+    pub fn get_gravity(&self) -> Vector {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vector) {
+        self.gravity = gravity;
+    }
+
+    /// Energy and momentum after the last step
+    pub fn get_diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    /// Diagnostics cost an O(n^2) pass for N-body and Coulomb potentials, large simulations can turn them off
+    pub fn set_diagnostics_enabled(&mut self, enabled: bool) {
+        self.diagnostics_enabled = enabled;
+        if !enabled {
+            self.diagnostics = Diagnostics::default();
         }
     }
 
-    pub fn update(&mut self, objects: &mut Vec<SimObject>, fixed_delta_time: time::Duration) {
-        for object in objects {
-            // get needed data from object
-            let mut old_pos = Vector::new(0.0, 0.0);
-            old_pos.x = object.x;
-            old_pos.y = object.y;
+    pub fn is_diagnostics_enabled(&self) -> bool {
+        self.diagnostics_enabled
+    }
+
+    /// Contacts found during the last step
+    pub fn get_contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    pub fn add_contacts(&mut self, contacts: Vec<Contact>) {
+        self.contacts.extend(contacts);
+    }
+
+    pub fn remove_contacts(&mut self, id: u128) {
+        self.contacts.retain(|contact| contact.a != id && contact.b != id);
+    }
+
+    pub fn restore_contacts(&mut self, contacts: Vec<Contact>) {
+        self.contacts = contacts;
+    }
+
+    pub fn add_fluid_volume(&mut self, fluid_volume: FluidVolume) {
+        self.fluid_volumes.push(fluid_volume);
+    }
+
+    pub fn add_force_field(&mut self, force_field: ForceField) {
+        self.force_fields.push(force_field);
+    }
+
+    pub fn update(&mut self, objects: &mut [SimObject], fixed_delta_time: time::Duration) {
+        let gravity_forces = self.calc_gravity_forces(objects);
+        let coulomb_forces = self.calc_coulomb_forces(objects);
+
+        for ((object, gravity_force), coulomb_force) in objects.iter_mut().zip(gravity_forces).zip(coulomb_forces) {
+            let rotation = object.transform.rotation.degrees;
+            let position = object.transform.position;
+
+            // Children follow their parent and aren't integrated
+            if object.parent.is_some() { continue; }
 
             //update physics object
-            let mut new_pos = Vector::new(0.0, 0.0);
-            match object.physics_object {
-                Some(ref mut phys_obj) => {
-                    if phys_obj.mass <= 0.0 { panic!("Mass must be greater than 0"); }
-
-                    // Process forces
-                    phys_obj.forces[0] = self.calc_gravity_force(phys_obj.mass);
-                    
-                    // Apply forces
-                    for i in 0..phys_obj.forces.len() {
-                        phys_obj.acceleration = self.calc_accel(phys_obj.forces[i], phys_obj.mass);
-                    }
+            let phys_obj = match object.physics_object {
+                Some(ref mut phys_obj) => phys_obj,
+                None => continue,
+            };
+
+            // Reported by Simulation::validate, skipped here so one bad object doesn't stop the frame
+            if !(phys_obj.mass > 0.0 && phys_obj.mass.is_finite()) { continue; }
 
-                    // Update positions and velocities
-                    phys_obj.velocity = self.calc_velo(phys_obj.acceleration, phys_obj.velocity, fixed_delta_time);
+            // Process forces
+            phys_obj.forces[0] = gravity_force;
+            let drag = self.calc_drag_force(phys_obj, rotation);
+            phys_obj.forces.push(drag);
+            for force_field in &self.force_fields {
+                if force_field.affects(phys_obj.layer) {
+                    phys_obj.forces.push(force_field.calc_force(position, phys_obj.mass));
+                }
+            }
+            if phys_obj.charge != 0.0 {
+                phys_obj.forces.push(coulomb_force);
+                phys_obj.forces.push(electro::calc_lorentz_force(phys_obj.charge, phys_obj.velocity, self.electric_field, self.magnetic_field));
+            }
+            for fluid_volume in &self.fluid_volumes {
+                if let Some((force, torque)) = fluid_volume.calc_force(&phys_obj.collider, position, rotation, phys_obj.velocity, self.gravity) {
+                    phys_obj.forces.push(force);
+                    phys_obj.torque += torque;
+                }
+            }
 
-                    let displacement = self.calc_displacement(phys_obj.velocity, fixed_delta_time);
-                    //println!("Displacement: {:?} {:?}", displacement.x, displacement.y);
-                    //println!("Gravity force: {:?} {:?}", phys_obj.forces[0].x, phys_obj.forces[0].y);
-                    new_pos = old_pos + displacement;
-                },
-                None => {},
+            // Apply forces
+            let mut net_force = Vector::new(0.0, 0.0);
+            for force in &phys_obj.forces {
+                net_force += *force;
             }
+            phys_obj.acceleration = self.calc_accel(net_force, phys_obj.mass);
+
+            let angular_accel = self.calc_angular_accel(phys_obj.torque, phys_obj.moment_of_inertia());
+
+            // Only gravity persists between steps
+            phys_obj.forces.truncate(1);
+            phys_obj.torque = 0.0;
+
+            // Update positions and velocities
+            phys_obj.velocity = self.calc_velo(phys_obj.acceleration, phys_obj.velocity, fixed_delta_time);
+            phys_obj.angular_velocity += angular_accel * fixed_delta_time.as_secs_f64();
+            phys_obj.angular_velocity = self.calc_angular_damping(phys_obj.angular_velocity, phys_obj.angular_damping, fixed_delta_time);
+
+            let displacement = self.calc_displacement(phys_obj.velocity, fixed_delta_time);
 
             // update object here
-            object.x = new_pos.x;
-            object.y = new_pos.y;
+            object.transform.position += displacement;
+            object.transform.rotation.degrees += phys_obj.angular_velocity * fixed_delta_time.as_secs_f64();
+        }
+
+        // Collision passes that run after this add their contacts
+        self.contacts.clear();
+    }
+
+    fn calc_gravity_forces(&mut self, objects: &[SimObject]) -> Vec<Vector> {
+        match &self.gravity_mode {
+            GravityMode::Uniform => {
+                objects.iter().map(|object| match object.physics_object {
+                    Some(ref phys_obj) => self.calc_gravity_force(phys_obj.mass),
+                    None => Vector::new(0.0, 0.0),
+                }).collect()
+            },
+            GravityMode::NBody { constant, softening, solver } => {
+                // Only objects with physics take part, map the results back to object indices
+                let mut indices = Vec::new();
+                let mut bodies = Vec::new();
+                for (i, object) in objects.iter().enumerate() {
+                    if let Some(ref phys_obj) = object.physics_object {
+                        indices.push(i);
+                        bodies.push(nbody::Body { position: object.transform.position, mass: phys_obj.mass });
+                    }
+                }
+
+                let mut forces = vec![Vector::new(0.0, 0.0); objects.len()];
+                for (i, force) in indices.into_iter().zip(nbody::calc_forces(&bodies, *constant, *softening, solver)) {
+                    forces[i] = force;
+                }
+                forces
+            },
+        }
+    }
+
+    fn calc_coulomb_forces(&self, objects: &[SimObject]) -> Vec<Vector> {
+        let mut forces = vec![Vector::new(0.0, 0.0); objects.len()];
+        if let CoulombMode::Direct { constant, softening } = self.coulomb_mode {
+            // Neutral objects are skipped entirely
+            let mut indices = Vec::new();
+            let mut charges = Vec::new();
+            for (i, object) in objects.iter().enumerate() {
+                if let Some(ref phys_obj) = object.physics_object {
+                    if phys_obj.charge != 0.0 {
+                        indices.push(i);
+                        charges.push(electro::Charge { position: object.transform.position, charge: phys_obj.charge });
+                    }
+                }
+            }
+
+            for (i, force) in indices.into_iter().zip(electro::calc_coulomb_forces(&charges, constant, softening)) {
+                forces[i] = force;
+            }
         }
+        forces
     }
 
-    fn calc_gravity_force(&mut self, mass: f64) -> Vector {
+    fn calc_gravity_force(&self, mass: f64) -> Vector {
         // F = ma, so F = mg
         self.gravity * mass
     }
 
+    fn calc_drag_force(&mut self, phys_obj: &PhysicsObject, rotation: f64) -> Vector {
+        let speed = phys_obj.velocity.get_mag();
+        if speed == 0.0 { return Vector::new(0.0, 0.0); }
+
+        // Stokes drag, F = -bv
+        let linear = phys_obj.velocity * -phys_obj.linear_drag;
+
+        // F = -1/2 * rho * Cd * A * |v| * v
+        let area = phys_obj.collider.cross_section(rotation, phys_obj.velocity.get_unit());
+        let quadratic = phys_obj.velocity * (-0.5 * self.medium_density * phys_obj.quadratic_drag * area * speed);
+
+        linear + quadratic
+    }
+
+    fn calc_angular_damping(&mut self, angular_velocity: f64, damping: f64, delta_time: time::Duration) -> f64 {
+        // w = w / (1 + c * t), stays stable for large c
+        angular_velocity / (1.0 + damping * delta_time.as_secs_f64())
+    }
+
+    fn calc_angular_accel(&self, torque: f64, moment_of_inertia: f64) -> f64 {
+        if moment_of_inertia <= 0.0 { return 0.0; }
+
+        // a = T / I, counter-clockwise radians to clockwise degrees
+        -(torque / moment_of_inertia).to_degrees()
+    }
+
     fn calc_accel(&mut self, force: Vector, mass: f64) -> Vector {
         // F = ma, so F / m = a
         force / mass
@@ -71,6 +273,7 @@ impl Physics {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct PhysicsObject {
     // forces[0] is always gravity
     collider: ColliderType,
@@ -78,22 +281,49 @@ pub struct PhysicsObject {
     velocity: Vector,
     acceleration: Vector,
     forces: Vec<Vector>,
+    // Counter-clockwise torque accumulated over one step
+    torque: f64,
+    // Degrees per second, clockwise like SimObject::rotation
+    pub angular_velocity: f64,
+    // Linear drag coefficient b in F = -bv
+    pub linear_drag: f64,
+    // Fraction of angular velocity lost per second
+    pub angular_damping: f64,
+    // Drag coefficient Cd, scaled by Physics::medium_density
+    pub quadratic_drag: f64,
+    // Collision layer bitmask
+    pub layer: u32,
+    // Electric charge (C)
+    pub charge: f64,
+    // 1 is perfectly elastic
+    pub restitution: f64,
 }
 
 impl PhysicsObject {
-    pub fn new(collider: ColliderType, mass: f64, velocity: Vector, acceleration: Vector) -> PhysicsObject {
+    pub fn new(collider: ColliderType, mass: f64, velocity: Vector, acceleration: Vector) -> Result<PhysicsObject, Error> {
+        if !(mass > 0.0 && mass.is_finite()) { return Err(Error::InvalidMass(mass)); }
+
         let mut forces: Vec<Vector> = Vec::new();
         forces.push(Vector::new(0.0, 0.0));
-        PhysicsObject {
+        Ok(PhysicsObject {
             collider: collider,
             mass: mass,
             velocity: velocity,
             acceleration: acceleration,
             forces: forces,
-        }
+            torque: 0.0,
+            angular_velocity: 0.0,
+            linear_drag: 0.0,
+            angular_damping: 0.0,
+            quadratic_drag: 0.0,
+            layer: 1,
+            charge: 0.0,
+            restitution: 1.0,
+        })
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub enum ColliderType {
     Circle {
         radius: f64,
@@ -107,3 +337,87 @@ pub enum ColliderType {
     },
 }
 
+impl PhysicsObject {
+    pub fn get_collider(&self) -> &ColliderType {
+        &self.collider
+    }
+
+    /// Acts for the next step only
+    pub fn apply_force(&mut self, force: Vector) {
+        self.forces.push(force);
+    }
+
+    pub fn get_mass(&self) -> f64 {
+        self.mass
+    }
+
+    pub fn get_velocity(&self) -> Vector {
+        self.velocity
+    }
+
+    pub fn set_velocity(&mut self, velocity: Vector) {
+        self.velocity = velocity;
+    }
+
+    pub fn moment_of_inertia(&self) -> f64 {
+        match self.collider {
+            ColliderType::Circle { radius } => 0.5 * self.mass * radius.powi(2),
+            ColliderType::Rectangle { width, height } => self.mass * (width.powi(2) + height.powi(2)) / 12.0,
+            ColliderType::Line { length } => self.mass * length.powi(2) / 12.0,
+        }
+    }
+}
+
+impl ColliderType {
+    /// Width of the collider as seen from `direction`, used as the drag reference area
+    pub fn cross_section(&self, rotation: f64, direction: Vector) -> f64 {
+        // Lines point along local y, same as the renderer
+        let rotation = Rot::from_degrees(rotation);
+        let (local_x, local_y) = (rotation.x_axis(), rotation.y_axis());
+        let normal = direction.perpendicular();
+
+        match self {
+            ColliderType::Circle { radius } => radius * 2.0,
+            ColliderType::Rectangle { width, height } => {
+                width * local_x.dot(normal).abs() + height * local_y.dot(normal).abs()
+            },
+            ColliderType::Line { length } => {
+                length * local_y.dot(normal).abs()
+            },
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        match self {
+            ColliderType::Circle { radius } => std::f64::consts::PI * radius.powi(2),
+            ColliderType::Rectangle { width, height } => width * height,
+            ColliderType::Line { .. } => 0.0,
+        }
+    }
+
+    /// World space outline, circles are approximated with 32 segments
+    pub fn to_polygon(&self, position: Vector, rotation: f64) -> Vec<Vector> {
+        let rotation = Rot::from_degrees(rotation);
+        let (local_x, local_y) = (rotation.x_axis(), rotation.y_axis());
+
+        match self {
+            ColliderType::Circle { radius } => {
+                (0..32).map(|i| {
+                    let theta = i as f64 / 32.0 * std::f64::consts::TAU;
+                    position + Vector::new(theta.cos(), theta.sin()) * *radius
+                }).collect()
+            },
+            ColliderType::Rectangle { width, height } => {
+                let half_x = local_x * (width / 2.0);
+                let half_y = local_y * (height / 2.0);
+                vec![
+                    position - half_x - half_y,
+                    position + half_x - half_y,
+                    position + half_x + half_y,
+                    position - half_x + half_y,
+                ]
+            },
+            ColliderType::Line { length } => vec![position, position + local_y * *length],
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use rayon::prelude::*;
+
+use crate::*;
+
+pub enum GravityMode {
+    // Every object falls with Physics::gravity
+    Uniform,
+    // Every object attracts every other object, F = G * m1 * m2 / (d^2 + softening^2)
+    NBody {
+        constant: f64,
+        softening: f64,
+        solver: NBodySolver,
+    },
+}
+
+pub enum NBodySolver {
+    // O(n^2), exact
+    Direct,
+    // O(n log n), theta is the opening angle, 0 is exact and ~0.5 is the usual tradeoff
+    BarnesHut {
+        theta: f64,
+    },
+}
+
+#[derive(Clone, Copy)]
+pub struct Body {
+    pub position: Vector,
+    pub mass: f64,
+}
+
+pub fn calc_forces(bodies: &[Body], constant: f64, softening: f64, solver: &NBodySolver) -> Vec<Vector> {
+    match solver {
+        NBodySolver::Direct => calc_forces_direct(bodies, constant, softening),
+        NBodySolver::BarnesHut { theta } => calc_forces_barnes_hut(bodies, constant, softening, *theta),
+    }
+}
+
+fn calc_forces_direct(bodies: &[Body], constant: f64, softening: f64) -> Vec<Vector> {
+    bodies.par_iter().enumerate().map(|(i, body)| {
+        let mut force = Vector::new(0.0, 0.0);
+        for (j, other) in bodies.iter().enumerate() {
+            if i == j { continue; }
+            force += calc_pair_force(body, other.position, other.mass, constant, softening);
+        }
+        force
+    }).collect()
+}
+
+fn calc_forces_barnes_hut(bodies: &[Body], constant: f64, softening: f64, theta: f64) -> Vec<Vector> {
+    if bodies.is_empty() { return Vec::new(); }
+
+    let tree = QuadTree::build(bodies);
+    bodies.par_iter().enumerate().map(|(i, body)| {
+        tree.calc_force(0, i, body, constant, softening, theta)
+    }).collect()
+}
+
+fn calc_pair_force(body: &Body, position: Vector, mass: f64, constant: f64, softening: f64) -> Vector {
+    // Plummer softening keeps close encounters from blowing up
+    let offset = position - body.position;
+    let dist_sq = offset.get_mag_squared() + softening.powi(2);
+    if dist_sq == 0.0 { return Vector::new(0.0, 0.0); }
+
+    offset * (constant * body.mass * mass / (dist_sq * dist_sq.sqrt()))
+}
+
+// Stops coincident bodies from subdividing forever
+const MAX_DEPTH: u32 = 32;
+
+struct Node {
+    center: Vector,
+    half_size: f64,
+    mass: f64,
+    center_of_mass: Vector,
+    children: Option<[usize; 4]>,
+    bodies: Vec<usize>,
+}
+
+impl Node {
+    fn new(center: Vector, half_size: f64) -> Node {
+        Node {
+            center,
+            half_size,
+            mass: 0.0,
+            center_of_mass: Vector::new(0.0, 0.0),
+            children: None,
+            bodies: Vec::new(),
+        }
+    }
+
+    fn contains(&self, position: Vector) -> bool {
+        (position.x - self.center.x).abs() <= self.half_size && (position.y - self.center.y).abs() <= self.half_size
+    }
+
+    fn quadrant(&self, position: Vector) -> usize {
+        let mut quadrant = 0;
+        if position.x >= self.center.x { quadrant += 1; }
+        if position.y >= self.center.y { quadrant += 2; }
+        quadrant
+    }
+}
+
+struct QuadTree<'a> {
+    nodes: Vec<Node>,
+    bodies: &'a [Body],
+}
+
+impl<'a> QuadTree<'a> {
+    fn build(bodies: &'a [Body]) -> QuadTree<'a> {
+        // Square root node around every body
+        let mut min = bodies[0].position;
+        let mut max = bodies[0].position;
+        for body in bodies {
+            min.x = min.x.min(body.position.x);
+            min.y = min.y.min(body.position.y);
+            max.x = max.x.max(body.position.x);
+            max.y = max.y.max(body.position.y);
+        }
+        let center = (min + max) / 2.0;
+        let half_size = ((max.x - min.x).max(max.y - min.y) / 2.0).max(f64::EPSILON);
+
+        let mut tree = QuadTree {
+            nodes: vec![Node::new(center, half_size)],
+            bodies,
+        };
+        for i in 0..bodies.len() {
+            tree.insert(0, i, 0);
+        }
+        tree
+    }
+
+    fn insert(&mut self, node: usize, body: usize, depth: u32) {
+        let position = self.bodies[body].position;
+        let mass = self.bodies[body].mass;
+
+        // Keep the running centre of mass for every node on the way down
+        let node_mass = self.nodes[node].mass;
+        let total_mass = node_mass + mass;
+        if total_mass > 0.0 {
+            self.nodes[node].center_of_mass = (self.nodes[node].center_of_mass * node_mass + position * mass) / total_mass;
+        }
+        self.nodes[node].mass = total_mass;
+
+        if let Some(children) = self.nodes[node].children {
+            let quadrant = self.nodes[node].quadrant(position);
+            self.insert(children[quadrant], body, depth + 1);
+            return;
+        }
+
+        if self.nodes[node].bodies.is_empty() || depth >= MAX_DEPTH {
+            self.nodes[node].bodies.push(body);
+            return;
+        }
+
+        // Occupied leaf, split it and push both bodies down
+        let children = self.subdivide(node);
+        let existing = std::mem::take(&mut self.nodes[node].bodies);
+        for other in existing {
+            let quadrant = self.nodes[node].quadrant(self.bodies[other].position);
+            self.place(children[quadrant], other);
+        }
+        let quadrant = self.nodes[node].quadrant(position);
+        self.insert(children[quadrant], body, depth + 1);
+    }
+
+    // Moves the single body of a split leaf into one of its fresh, empty children
+    fn place(&mut self, node: usize, body: usize) {
+        self.nodes[node].mass = self.bodies[body].mass;
+        self.nodes[node].center_of_mass = self.bodies[body].position;
+        self.nodes[node].bodies.push(body);
+    }
+
+    fn subdivide(&mut self, node: usize) -> [usize; 4] {
+        let center = self.nodes[node].center;
+        let half_size = self.nodes[node].half_size / 2.0;
+        let mut children = [0; 4];
+        for (quadrant, child) in children.iter_mut().enumerate() {
+            let x = if quadrant & 1 == 1 { center.x + half_size } else { center.x - half_size };
+            let y = if quadrant & 2 == 2 { center.y + half_size } else { center.y - half_size };
+            *child = self.nodes.len();
+            self.nodes.push(Node::new(Vector::new(x, y), half_size));
+        }
+        self.nodes[node].children = Some(children);
+        children
+    }
+
+    fn calc_force(&self, node: usize, index: usize, body: &Body, constant: f64, softening: f64, theta: f64) -> Vector {
+        let current = &self.nodes[node];
+        if current.mass == 0.0 { return Vector::new(0.0, 0.0); }
+
+        match current.children {
+            None => {
+                let mut force = Vector::new(0.0, 0.0);
+                for &other in &current.bodies {
+                    if other == index { continue; }
+                    force += calc_pair_force(body, self.bodies[other].position, self.bodies[other].mass, constant, softening);
+                }
+                force
+            },
+            Some(children) => {
+                // s / d < theta means the node is far enough away to treat as one mass
+                let distance = (current.center_of_mass - body.position).get_mag();
+                if !current.contains(body.position) && current.half_size * 2.0 < theta * distance {
+                    return calc_pair_force(body, current.center_of_mass, current.mass, constant, softening);
+                }
+
+                let mut force = Vector::new(0.0, 0.0);
+                for child in children {
+                    force += self.calc_force(child, index, body, constant, softening, theta);
+                }
+                force
+            },
+        }
+    }
+}
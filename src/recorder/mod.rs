@@ -0,0 +1,207 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::*;
+use physics::Physics;
+
+/// Samples a set of named quantities every fixed step for export to CSV or JSON lines
+pub struct Recorder {
+    // Fixed once sampling starts so every row has the same columns
+    channels: Vec<Channel>,
+    // Record every nth fixed step, 1 records all of them
+    pub interval: u64,
+    samples: Vec<Sample>,
+}
+
+pub struct Channel {
+    pub name: String,
+    pub quantity: Quantity,
+}
+
+pub type Probe = Box<dyn Fn(&[SimObject], &Physics) -> f64>;
+
+pub enum Quantity {
+    X(u128),
+    Y(u128),
+    Rotation(u128),
+    VelocityX(u128),
+    VelocityY(u128),
+    Speed(u128),
+    KineticEnergy(u128),
+    TotalKineticEnergy,
+    TotalEnergy,
+    // Anything else, e.g. the distance between two objects
+    Probe(Probe),
+}
+
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+}
+
+struct Sample {
+    step: u64,
+    time: f64,
+    values: Vec<f64>,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder {
+            channels: Vec::new(),
+            interval: 1,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Channels can only be added before the first sample or after `clear`
+    pub fn add_channel(&mut self, name: &str, quantity: Quantity) -> Result<(), Error> {
+        if !self.samples.is_empty() { return Err(Error::RecordingStarted); }
+
+        self.channels.push(Channel { name: name.to_string(), quantity });
+        Ok(())
+    }
+
+    /// Position, velocity, rotation and kinetic energy of one object, prefixed with `name`
+    pub fn track_object(&mut self, name: &str, id: u128) -> Result<(), Error> {
+        self.add_channel(&format!("{}.x", name), Quantity::X(id))?;
+        self.add_channel(&format!("{}.y", name), Quantity::Y(id))?;
+        self.add_channel(&format!("{}.vx", name), Quantity::VelocityX(id))?;
+        self.add_channel(&format!("{}.vy", name), Quantity::VelocityY(id))?;
+        self.add_channel(&format!("{}.rotation", name), Quantity::Rotation(id))?;
+        self.add_channel(&format!("{}.kinetic", name), Quantity::KineticEnergy(id))
+    }
+
+    pub fn get_channels(&self) -> &[Channel] {
+        &self.channels
+    }
+
+    pub(crate) fn needs_diagnostics(&self) -> bool {
+        self.channels.iter().any(|channel| matches!(channel.quantity, Quantity::KineticEnergy(_) | Quantity::TotalKineticEnergy | Quantity::TotalEnergy))
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Positions and rotations are read as they are, `Simulation` records while the objects are in world space
+    pub fn record(&mut self, step: u64, time: f64, objects: &registry::ObjectRegistry, physics: &Physics) {
+        if !step.is_multiple_of(self.interval.max(1)) { return; }
+
+        let values = self.channels.iter().map(|channel| sample_quantity(&channel.quantity, objects, physics)).collect();
+        self.samples.push(Sample { step, time, values });
+    }
+
+    /// One header row, then one row per sample. Missing objects are left empty
+    pub fn write_csv<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "step,time")?;
+        for channel in &self.channels {
+            write!(writer, ",{}", escape_csv(&channel.name))?;
+        }
+        writeln!(writer)?;
+
+        for sample in &self.samples {
+            write!(writer, "{},{}", sample.step, sample.time)?;
+            for value in &sample.values {
+                if value.is_finite() {
+                    write!(writer, ",{}", value)?;
+                } else {
+                    write!(writer, ",")?;
+                }
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// One JSON object per line. Missing objects are written as null
+    pub fn write_json_lines<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for sample in &self.samples {
+            write!(writer, "{{\"step\":{},\"time\":{}", sample.step, sample.time)?;
+            for (channel, value) in self.channels.iter().zip(&sample.values) {
+                write!(writer, ",")?;
+                serde_json::to_writer(&mut *writer, &channel.name)?;
+                if value.is_finite() {
+                    write!(writer, ":{}", value)?;
+                } else {
+                    write!(writer, ":null")?;
+                }
+            }
+            writeln!(writer, "}}")?;
+        }
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: ExportFormat) -> io::Result<()> {
+        let mut writer = io::BufWriter::new(fs::File::create(path)?);
+        match format {
+            ExportFormat::Csv => self.write_csv(&mut writer)?,
+            ExportFormat::JsonLines => self.write_json_lines(&mut writer)?,
+        }
+        writer.flush()
+    }
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn sample_quantity(quantity: &Quantity, objects: &registry::ObjectRegistry, physics: &Physics) -> f64 {
+    let find = |id: &u128| objects.get_by_id(*id);
+    let velocity = |id: &u128| find(id).and_then(|object| object.physics_object.as_ref()).map(|phys_obj| phys_obj.get_velocity());
+
+    match quantity {
+        Quantity::X(id) => find(id).map_or(f64::NAN, |object| object.transform.position.x),
+        Quantity::Y(id) => find(id).map_or(f64::NAN, |object| object.transform.position.y),
+        Quantity::Rotation(id) => find(id).map_or(f64::NAN, |object| object.transform.rotation.degrees),
+        Quantity::VelocityX(id) => velocity(id).map_or(f64::NAN, |velocity| velocity.x),
+        Quantity::VelocityY(id) => velocity(id).map_or(f64::NAN, |velocity| velocity.y),
+        Quantity::Speed(id) => velocity(id).map_or(f64::NAN, |velocity| velocity.get_mag()),
+        Quantity::KineticEnergy(id) => physics.get_diagnostics().get_object(*id).map_or(f64::NAN, |object| object.kinetic + object.rotational),
+        Quantity::TotalKineticEnergy => physics.get_diagnostics().kinetic + physics.get_diagnostics().rotational,
+        Quantity::TotalEnergy => physics.get_diagnostics().get_total_energy(),
+        Quantity::Probe(probe) => probe(objects, physics),
+    }
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn children_are_recorded_in_world_space() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::ZERO)).unwrap();
+        let parent = simulation.add_object(SimObject::new(10.0, 5.0, 0.0, None, None, None, None)).unwrap();
+        let child = simulation.add_object(SimObject::new(1.0, 2.0, 0.0, None, None, None, None)).unwrap();
+        simulation.set_parent(child, Some(parent)).unwrap();
+        let child_id = simulation.objects.get(child).unwrap().id;
+
+        let mut recorder = Recorder::new();
+        recorder.add_channel("x", Quantity::X(child_id)).unwrap();
+        recorder.add_channel("y", Quantity::Y(child_id)).unwrap();
+        recorder.add_channel("missing", Quantity::X(0)).unwrap();
+        simulation.recorder = Some(recorder);
+        simulation.step_once();
+
+        let values = &simulation.recorder.as_ref().unwrap().samples[0].values;
+        assert_eq!(values[..2], [11.0, 7.0]);
+        assert!(values[2].is_nan());
+        // The child keeps its local transform
+        assert_eq!(simulation.objects.get(child).unwrap().transform.position, Vector::new(1.0, 2.0));
+    }
+}
@@ -0,0 +1,368 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::*;
+
+/// Stable reference to an object, goes stale once the object is removed even if the slot is reused
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct Handle {
+    index: u32,
+    generation: u32,
+}
+
+struct Slot {
+    generation: u32,
+    // Position in `objects`, None while the slot is free
+    position: Option<usize>,
+}
+
+/// Owns every SimObject. Objects are stored densely and removal swaps the last object into the gap,
+/// handles and UUIDs resolve to them in O(1)
+#[derive(Default)]
+pub struct ObjectRegistry {
+    objects: Vec<SimObject>,
+    // Handle of the object at the same position in `objects`
+    handles: Vec<Handle>,
+    // When the object at the same position was added, for insertion order
+    sequence: Vec<u64>,
+    next_sequence: u64,
+    slots: Vec<Slot>,
+    free_slots: Vec<u32>,
+    ids: HashMap<u128, Handle>,
+    // Ids come from this counter instead of UUIDs while recording or replaying
+    sequential_ids: bool,
+    next_id: u128,
+}
+
+impl ObjectRegistry {
+    pub fn new() -> ObjectRegistry {
+        ObjectRegistry::default()
+    }
+
+    /// Fails if the id is already registered.
+    /// While ids are sequential every object gets the next id from the counter instead
+    pub fn add(&mut self, mut object: SimObject) -> Result<Handle, Error> {
+        if self.sequential_ids {
+            object.id = self.next_id;
+            self.next_id += 1;
+        } else if self.ids.contains_key(&object.id) {
+            return Err(Error::DuplicateId(object.id));
+        }
+        Ok(self.insert(object))
+    }
+
+    // Registers the object under its current id
+    fn insert(&mut self, object: SimObject) -> Handle {
+        let handle = match self.free_slots.pop() {
+            Some(index) => Handle { index, generation: self.slots[index as usize].generation },
+            None => {
+                self.slots.push(Slot { generation: 0, position: None });
+                Handle { index: self.slots.len() as u32 - 1, generation: 0 }
+            },
+        };
+
+        self.slots[handle.index as usize].position = Some(self.objects.len());
+        self.ids.insert(object.id, handle);
+        self.handles.push(handle);
+        self.sequence.push(self.next_sequence);
+        self.next_sequence += 1;
+        self.objects.push(object);
+        handle
+    }
+
+    /// O(1), the last object moves into the gap.
+    /// Only the simulation removes objects so `on_destroy` always runs, see `Simulation::remove_object`
+    pub(crate) fn remove(&mut self, handle: Handle) -> Option<SimObject> {
+        let position = self.get_position(handle)?;
+
+        let slot = &mut self.slots[handle.index as usize];
+        slot.position = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(handle.index);
+
+        self.handles.swap_remove(position);
+        self.sequence.swap_remove(position);
+        if let Some(moved) = self.handles.get(position) {
+            self.slots[moved.index as usize].position = Some(position);
+        }
+
+        let object = self.objects.swap_remove(position);
+        self.ids.remove(&object.id);
+        Some(object)
+    }
+
+    /// Swaps in a new set of objects, objects whose id was already registered keep their handle.
+    /// Returns the objects that are gone, fails without changing anything if two of the objects share an id
+    pub(crate) fn replace_all(&mut self, objects: Vec<SimObject>) -> Result<Vec<SimObject>, Error> {
+        let mut seen = std::collections::HashSet::new();
+        if let Some(object) = objects.iter().find(|object| !seen.insert(object.id)) {
+            return Err(Error::DuplicateId(object.id));
+        }
+
+        let mut old_ids = std::mem::take(&mut self.ids);
+        let old_objects = std::mem::take(&mut self.objects);
+        self.handles.clear();
+        self.sequence.clear();
+
+        for object in objects {
+            let Some(handle) = old_ids.remove(&object.id) else {
+                self.insert(object);
+                continue;
+            };
+            self.slots[handle.index as usize].position = Some(self.objects.len());
+            self.ids.insert(object.id, handle);
+            self.handles.push(handle);
+            self.sequence.push(self.next_sequence);
+            self.next_sequence += 1;
+            self.objects.push(object);
+        }
+
+        for handle in old_ids.values() {
+            let slot = &mut self.slots[handle.index as usize];
+            slot.position = None;
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free_slots.push(handle.index);
+        }
+        Ok(old_objects.into_iter().filter(|object| old_ids.contains_key(&object.id)).collect())
+    }
+
+    /// Recordings and replays hand out ids from a counter so both runs give spawned objects the same ids.
+    /// UUIDs are v4, which a small counter never collides with
+    pub(crate) fn set_sequential_ids(&mut self, sequential_ids: bool) {
+        self.sequential_ids = sequential_ids;
+    }
+
+    pub(crate) fn get_next_id(&self) -> u128 {
+        self.next_id
+    }
+
+    pub(crate) fn set_next_id(&mut self, next_id: u128) {
+        self.next_id = next_id;
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&SimObject> {
+        self.get_position(handle).map(|position| &self.objects[position])
+    }
+
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut SimObject> {
+        self.get_position(handle).map(|position| &mut self.objects[position])
+    }
+
+    pub fn get_by_id(&self, id: u128) -> Option<&SimObject> {
+        self.get(self.get_handle(id)?)
+    }
+
+    pub fn get_by_id_mut(&mut self, id: u128) -> Option<&mut SimObject> {
+        self.get_mut(self.get_handle(id)?)
+    }
+
+    pub fn get_handle(&self, id: u128) -> Option<Handle> {
+        self.ids.get(&id).copied()
+    }
+
+    pub fn contains(&self, handle: Handle) -> bool {
+        self.get_position(handle).is_some()
+    }
+
+    /// Handles in storage order, matching the order of the objects
+    pub fn handles(&self) -> &[Handle] {
+        &self.handles
+    }
+
+    /// Mutable iteration in storage order. Ids are private, so objects can't be moved to another id this way
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, SimObject> {
+        self.objects.iter_mut()
+    }
+
+    /// For systems that work on every object at once, they must not reorder or replace objects
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [SimObject] {
+        &mut self.objects
+    }
+
+    /// Oldest first, storage order changes when objects are removed
+    pub fn iter_insertion_order(&self) -> impl Iterator<Item = &SimObject> {
+        self.insertion_order().into_iter().map(move |i| &self.objects[i])
+    }
+
+    /// Back to front by `RenderObject::z_index`, ties keep insertion order
+    pub fn iter_render_order(&self) -> impl Iterator<Item = &SimObject> {
+        let mut order = self.insertion_order();
+        order.sort_by_key(|&i| self.objects[i].render_object.as_ref().map_or(0, |render_object| render_object.z_index));
+        order.into_iter().map(move |i| &self.objects[i])
+    }
+
+    fn insertion_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.objects.len()).collect();
+        order.sort_by_key(|&i| self.sequence[i]);
+        order
+    }
+
+    /// Composes the transforms of every parent
+    pub fn get_world_transform(&self, object: &SimObject) -> Transform {
+        let mut transform = object.transform;
+        let mut parent_id = object.parent;
+
+        // Bounded in case a cycle was made by editing `parent` directly
+        for _ in 0..self.objects.len() {
+            let parent = match parent_id.and_then(|id| self.get_by_id(id)) {
+                Some(parent) => parent,
+                None => break,
+            };
+            transform = parent.transform.compose(transform);
+            parent_id = parent.parent;
+        }
+        transform
+    }
+
+    /// Direct children only
+    pub fn get_children(&self, id: u128) -> Vec<Handle> {
+        self.objects.iter().zip(&self.handles).filter(|(object, _)| object.parent == Some(id)).map(|(_, handle)| *handle).collect()
+    }
+
+    pub fn is_ancestor(&self, ancestor: u128, object: &SimObject) -> bool {
+        let mut parent_id = object.parent;
+        for _ in 0..self.objects.len() {
+            match parent_id {
+                Some(id) if id == ancestor => return true,
+                Some(id) => parent_id = self.get_by_id(id).and_then(|parent| parent.parent),
+                None => return false,
+            }
+        }
+        false
+    }
+
+    /// Moves every child to world space so systems that read the transform see world positions.
+    /// Returns the local transforms for `leave_world_space`
+    pub(crate) fn enter_world_space(&mut self) -> Vec<(usize, Transform)> {
+        let locals: Vec<(usize, Transform)> = self.objects.iter().enumerate()
+            .filter(|(_, object)| object.parent.is_some())
+            .map(|(i, object)| (i, object.transform))
+            .collect();
+        let worlds: Vec<Transform> = locals.iter().map(|local| self.get_world_transform(&self.objects[local.0])).collect();
+
+        for (local, world) in locals.iter().zip(worlds) {
+            self.objects[local.0].transform = world;
+        }
+        locals
+    }
+
+    pub(crate) fn leave_world_space(&mut self, locals: Vec<(usize, Transform)>) {
+        for (i, transform) in locals {
+            self.objects[i].transform = transform;
+        }
+    }
+
+    /// Oldest object with the name
+    pub fn find_by_name(&self, name: &str) -> Option<Handle> {
+        (0..self.objects.len())
+            .filter(|&i| self.objects[i].name.as_deref() == Some(name))
+            .min_by_key(|&i| self.sequence[i])
+            .map(|i| self.handles[i])
+    }
+
+    pub fn find_with_tag(&self, tag: &str) -> Vec<Handle> {
+        self.find(|object| object.tags.contains(tag)).collect()
+    }
+
+    /// Uses world positions, so children are found where they are drawn
+    pub fn find_within_radius(&self, center: Vector, radius: f64) -> Vec<Handle> {
+        self.find(|object| self.get_world_transform(object).position.distance(center) <= radius).collect()
+    }
+
+    pub fn find_with_physics(&self) -> Vec<Handle> {
+        self.find(|object| object.physics_object.is_some()).collect()
+    }
+
+    fn find<'a>(&'a self, predicate: impl Fn(&SimObject) -> bool + 'a) -> impl Iterator<Item = Handle> + 'a {
+        self.objects.iter().zip(&self.handles).filter(move |(object, _)| predicate(object)).map(|(_, handle)| *handle)
+    }
+
+    /// Every object holding a component of type T, in storage order
+    pub fn with_component<T: 'static>(&self) -> impl Iterator<Item = (&SimObject, &T)> {
+        self.objects.iter().filter_map(|object| object.get_component::<T>().map(|component| (object, component)))
+    }
+
+    pub fn with_component_mut<T: 'static>(&mut self) -> impl Iterator<Item = &mut SimObject> {
+        self.objects.iter_mut().filter(|object| object.components.contains::<T>())
+    }
+
+    fn get_position(&self, handle: Handle) -> Option<usize> {
+        let slot = self.slots.get(handle.index as usize)?;
+        if slot.generation == handle.generation { slot.position } else { None }
+    }
+}
+
+// Objects can be read like a slice in storage order, `iter_mut` and `get_mut` change them
+impl std::ops::Deref for ObjectRegistry {
+    type Target = [SimObject];
+
+    fn deref(&self) -> &[SimObject] {
+        &self.objects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(name: &str) -> SimObject {
+        SimObject::new(0.0, 0.0, 0.0, None, None, None, None).with_name(name)
+    }
+
+    #[test]
+    fn stale_handle_after_remove_and_readd() {
+        let mut registry = ObjectRegistry::new();
+        let a = registry.add(object("a")).unwrap();
+        let removed = registry.remove(a).unwrap();
+        assert!(registry.get(a).is_none());
+
+        // Same object, same id, but the old handle stays stale
+        let id = removed.get_id();
+        let readded = registry.add(removed).unwrap();
+        assert_ne!(readded, a);
+        assert!(registry.get(a).is_none());
+        assert!(!registry.contains(a));
+        assert!(registry.remove(a).is_none());
+        assert_eq!(registry.get_handle(id), Some(readded));
+    }
+
+    #[test]
+    fn slots_are_reused_with_a_new_generation() {
+        let mut registry = ObjectRegistry::new();
+        let a = registry.add(object("a")).unwrap();
+        registry.remove(a);
+
+        let b = registry.add(object("b")).unwrap();
+        assert_eq!(b.index, a.index);
+        assert_ne!(b.generation, a.generation);
+        assert!(registry.get(a).is_none());
+        assert_eq!(registry.get(b).unwrap().name.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn remove_keeps_other_handles_valid() {
+        let mut registry = ObjectRegistry::new();
+        let handles: Vec<Handle> = ["a", "b", "c", "d"].iter().map(|name| registry.add(object(name)).unwrap()).collect();
+        registry.remove(handles[1]);
+
+        // d was swapped into b's place, every other handle still resolves
+        for (handle, name) in [(handles[0], "a"), (handles[2], "c"), (handles[3], "d")] {
+            assert_eq!(registry.get(handle).unwrap().name.as_deref(), Some(name));
+            let id = registry.get(handle).unwrap().get_id();
+            assert_eq!(registry.get_handle(id), Some(handle));
+        }
+        let names: Vec<&str> = registry.iter_insertion_order().map(|object| object.name.as_deref().unwrap()).collect();
+        assert_eq!(names, ["a", "c", "d"]);
+    }
+
+    #[test]
+    fn duplicate_ids_are_rejected() {
+        let mut registry = ObjectRegistry::new();
+        let a = registry.add(object("a")).unwrap();
+        let mut copy = object("copy");
+        copy.id = registry.get(a).unwrap().get_id();
+
+        assert!(matches!(registry.add(copy), Err(Error::DuplicateId(_))));
+        assert_eq!(registry.len(), 1);
+    }
+}
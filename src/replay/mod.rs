@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use crate::*;
+use snapshot::{ObjectSnapshot, Snapshot};
+
+/// Something from outside the simulation that changed it during a tick
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Input {
+    Spawn(Box<ObjectSnapshot>),
+    ApplyForce {
+        id: u128,
+        force: Vector,
+    },
+    Command(String),
+    // From `Simulation::remove_object` between steps
+    Despawn(u128),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Tick {
+    pub step: u64,
+    pub inputs: Vec<Input>,
+    // State hash after the step ran
+    pub hash: u64,
+}
+
+/// Everything needed to reproduce a run: the starting state plus every input, tick by tick
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InputLog {
+    pub initial: Snapshot,
+    pub time_step: i32,
+    pub ticks: Vec<Tick>,
+}
+
+pub struct Replay {
+    log: InputLog,
+    next_tick: usize,
+    mismatches: Vec<u64>,
+}
+
+impl InputLog {
+    pub fn new(initial: Snapshot, time_step: i32) -> InputLog {
+        InputLog {
+            initial,
+            time_step,
+            ticks: Vec::new(),
+        }
+    }
+
+    /// Ticks without inputs are stored too, so every step has a hash to compare
+    pub fn record_tick(&mut self, step: u64, inputs: Vec<Input>, hash: u64) {
+        self.ticks.push(Tick { step, inputs, hash });
+    }
+
+    /// Callbacks can't be stored, a log read from disk replays without them
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<InputLog, Error> {
+        ron::from_str(&fs::read_to_string(path)?).map_err(|error| Error::Parse(error.to_string()))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let text = ron::to_string(self).map_err(|error| Error::Serialize(error.to_string()))?;
+        fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+impl Replay {
+    pub fn new(log: InputLog) -> Replay {
+        Replay {
+            log,
+            next_tick: 0,
+            mismatches: Vec::new(),
+        }
+    }
+
+    pub fn get_log(&self) -> &InputLog {
+        &self.log
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_tick >= self.log.ticks.len()
+    }
+
+    /// Steps whose state hash differed from the recording
+    pub fn get_mismatches(&self) -> &[u64] {
+        &self.mismatches
+    }
+
+    pub fn take_inputs(&mut self, step: u64) -> Vec<Input> {
+        match self.log.ticks.get(self.next_tick) {
+            Some(tick) if tick.step == step => tick.inputs.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn verify(&mut self, step: u64, hash: u64) {
+        if let Some(tick) = self.log.ticks.get(self.next_tick) {
+            if tick.step == step {
+                if tick.hash != hash {
+                    self.mismatches.push(step);
+                }
+                self.next_tick += 1;
+            }
+        }
+    }
+}
+
+/// Hash of the object state, bit-exact so any floating point divergence shows up
+pub fn hash_state(step: u64, objects: &[SimObject]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    step.hash(&mut hasher);
+    for object in objects {
+        object.id.hash(&mut hasher);
+        object.transform.position.x.to_bits().hash(&mut hasher);
+        object.transform.position.y.to_bits().hash(&mut hasher);
+        object.transform.rotation.degrees.to_bits().hash(&mut hasher);
+        if let Some(ref phys_obj) = object.physics_object {
+            let velocity = phys_obj.get_velocity();
+            velocity.x.to_bits().hash(&mut hasher);
+            velocity.y.to_bits().hash(&mut hasher);
+            phys_obj.angular_velocity.to_bits().hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use physics::{ColliderType, Physics, PhysicsObject};
+
+    const STEPS: usize = 12;
+
+    fn ball(x: f64, y: f64) -> SimObject {
+        let phys_obj = PhysicsObject::new(ColliderType::Circle { radius: 1.0 }, 1.0, Vector::new(1.0, 2.0), Vector::ZERO).unwrap();
+        SimObject::new(x, y, 0.0, None, Some(phys_obj), None, None)
+    }
+
+    // New ids are handed out mid-step, so the replay only matches if it hands out the same ones
+    fn spawn_ball(object: &mut SimObject, context: &mut commands::Context) {
+        context.spawn(ball(object.transform.position.x, 50.0));
+    }
+
+    fn state(simulation: &Simulation) -> Vec<(u128, Vector)> {
+        simulation.objects.iter().map(|object| (object.id, object.transform.position)).collect()
+    }
+
+    fn record(simulation: &mut Simulation) -> InputLog {
+        simulation.start_input_recording();
+        for step in 0..STEPS {
+            match step {
+                2 => simulation.spawn(ball(5.0, 0.0).with_name("spawned")).unwrap(),
+                4 => {
+                    let id = simulation.objects.get(simulation.find_by_name("spawned").unwrap()).unwrap().id;
+                    simulation.apply_force(id, Vector::new(10.0, 0.0)).unwrap();
+                },
+                5 => { simulation.add_object(ball(-5.0, 0.0).with_name("added")).unwrap(); },
+                8 => { simulation.remove_object(simulation.find_by_name("added").unwrap()); },
+                _ => {},
+            }
+            simulation.step_once();
+        }
+        simulation.stop_input_recording().unwrap()
+    }
+
+    #[test]
+    fn replay_matches_recording() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::new(0.0, -9.8))).unwrap();
+        simulation.add_object(SimObject::new(0.0, 0.0, 0.0, None, None, None, Some(spawn_ball)).with_name("spawner")).unwrap();
+
+        let log = record(&mut simulation);
+        assert_eq!(log.ticks.len(), STEPS);
+        let recorded = state(&simulation);
+
+        simulation.start_replay(log).unwrap();
+        for _ in 0..STEPS {
+            simulation.step_once();
+        }
+
+        let replay = simulation.get_replay().unwrap();
+        assert!(replay.is_finished());
+        assert!(replay.get_mismatches().is_empty(), "mismatched steps {:?}", replay.get_mismatches());
+        assert_eq!(state(&simulation), recorded);
+    }
+
+    #[test]
+    fn live_inputs_are_rejected_during_replay() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::ZERO)).unwrap();
+        let log = record(&mut simulation);
+
+        simulation.start_replay(log).unwrap();
+        assert!(matches!(simulation.spawn(ball(0.0, 0.0)), Err(Error::ReplayRunning)));
+        assert!(matches!(simulation.apply_force(0, Vector::ZERO), Err(Error::ReplayRunning)));
+        assert!(matches!(simulation.command("reset"), Err(Error::ReplayRunning)));
+
+        simulation.stop_replay();
+        assert!(simulation.spawn(ball(0.0, 0.0)).is_ok());
+    }
+
+    #[test]
+    fn failed_spawns_are_reported() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::ZERO)).unwrap();
+        let handle = simulation.add_object(ball(0.0, 0.0)).unwrap();
+        let id = simulation.objects.get(handle).unwrap().id;
+        let mut duplicate = ball(5.0, 0.0);
+        duplicate.id = id;
+
+        simulation.spawn(duplicate).unwrap();
+        simulation.step_once();
+        let errors = simulation.take_errors();
+        assert!(matches!(errors[..], [Error::DuplicateId(duplicate_id)] if duplicate_id == id));
+        assert!(simulation.take_errors().is_empty());
+    }
+}
@@ -0,0 +1,312 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::*;
+use graphics::{RenderObject, RenderType};
+use physics::{ColliderType, PhysicsObject};
+
+/// Human-editable description of a world, stored as RON
+///
+/// ```ron
+/// Scene(
+///     gravity: (0.0, -9.807),
+///     time_step: 8333,
+///     objects: [
+///         (
+///             x: 10.0,
+///             y: 50.0,
+///             render: Some((color: (255, 0, 0, 255), render_type: Line(magnitude: 12.0))),
+///             physics: Some((collider: Circle(radius: 15.0), mass: 1.0)),
+///         ),
+///     ],
+/// )
+/// ```
+#[derive(Serialize, Deserialize)]
+pub struct Scene {
+    pub gravity: (f64, f64),
+    // Microseconds, same as Simulation::time_step
+    pub time_step: i32,
+    #[serde(default)]
+    pub medium_density: f64,
+    #[serde(default)]
+    pub objects: Vec<SceneObject>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SceneObject {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub x: f64,
+    pub y: f64,
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default)]
+    pub render: Option<SceneRender>,
+    #[serde(default)]
+    pub physics: Option<ScenePhysics>,
+    // Index of the parent in `objects`
+    #[serde(default)]
+    pub parent: Option<usize>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SceneRender {
+    // (r, g, b, a)
+    pub color: (u8, u8, u8, u8),
+    pub render_type: SceneRenderType,
+    #[serde(default)]
+    pub z_index: i32,
+}
+
+// Mirrors RenderType, textures can't be stored and are saved as None
+#[derive(Clone, Serialize, Deserialize)]
+pub enum SceneRenderType {
+    Line { magnitude: f64 },
+    Circle { radius: i32, #[serde(default)] segments: i32 },
+    Polygon { vertices: Vec<(u32, u32)> },
+    Point,
+    Rectangle { width: i32, height: i32, #[serde(default)] filled: bool },
+    None,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ScenePhysics {
+    pub collider: SceneCollider,
+    pub mass: f64,
+    #[serde(default)]
+    pub velocity: (f64, f64),
+    #[serde(default)]
+    pub angular_velocity: f64,
+    #[serde(default)]
+    pub linear_drag: f64,
+    #[serde(default)]
+    pub angular_damping: f64,
+    #[serde(default)]
+    pub quadratic_drag: f64,
+    #[serde(default = "default_layer")]
+    pub layer: u32,
+    #[serde(default)]
+    pub charge: f64,
+    #[serde(default = "default_restitution")]
+    pub restitution: f64,
+}
+
+// Mirrors ColliderType
+#[derive(Serialize, Deserialize)]
+pub enum SceneCollider {
+    Circle { radius: f64 },
+    Rectangle { width: f64, height: f64 },
+    Line { length: f64 },
+}
+
+fn default_layer() -> u32 {
+    1
+}
+
+fn default_restitution() -> f64 {
+    1.0
+}
+
+impl Scene {
+    pub fn parse(text: &str) -> Result<Scene, Error> {
+        ron::from_str(text).map_err(|error| Error::Parse(error.to_string()))
+    }
+
+    pub fn to_ron(&self) -> Result<String, Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|error| Error::Serialize(error.to_string()))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, Error> {
+        Scene::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+
+    /// Captures the objects, gravity and timestep of a running simulation
+    pub fn capture(simulation: &Simulation) -> Scene {
+        let gravity = simulation.physics.get_gravity();
+        Scene {
+            gravity: (gravity.x, gravity.y),
+            time_step: simulation.time_step,
+            medium_density: simulation.physics.medium_density,
+            objects: simulation.objects.iter().map(|object| {
+                let mut scene_object = SceneObject::capture(object);
+                scene_object.parent = object.parent.and_then(|id| simulation.objects.iter().position(|parent| parent.id == id));
+                scene_object
+            }).collect(),
+        }
+    }
+
+    pub fn build_objects(&self) -> Result<Vec<SimObject>, Error> {
+        let mut objects: Vec<SimObject> = self.objects.iter().map(SceneObject::build).collect::<Result<_, _>>()?;
+        for (i, scene_object) in self.objects.iter().enumerate() {
+            objects[i].parent = scene_object.parent.and_then(|parent| objects.get(parent)).map(|parent| parent.id);
+        }
+        Ok(objects)
+    }
+}
+
+impl SceneObject {
+    pub fn capture(object: &SimObject) -> SceneObject {
+        SceneObject {
+            name: object.name.clone(),
+            tags: sorted_tags(object),
+            x: object.transform.position.x,
+            y: object.transform.position.y,
+            rotation: object.transform.rotation.degrees,
+            render: object.render_object.as_ref().map(SceneRender::capture),
+            parent: None,
+            physics: object.physics_object.as_ref().map(|phys_obj| {
+                let velocity = phys_obj.get_velocity();
+                ScenePhysics {
+                    collider: match phys_obj.get_collider() {
+                        ColliderType::Circle { radius } => SceneCollider::Circle { radius: *radius },
+                        ColliderType::Rectangle { width, height } => SceneCollider::Rectangle { width: *width, height: *height },
+                        ColliderType::Line { length } => SceneCollider::Line { length: *length },
+                    },
+                    mass: phys_obj.get_mass(),
+                    velocity: (velocity.x, velocity.y),
+                    angular_velocity: phys_obj.angular_velocity,
+                    linear_drag: phys_obj.linear_drag,
+                    angular_damping: phys_obj.angular_damping,
+                    quadratic_drag: phys_obj.quadratic_drag,
+                    layer: phys_obj.layer,
+                    charge: phys_obj.charge,
+                    restitution: phys_obj.restitution,
+                }
+            }),
+        }
+    }
+
+    pub fn build(&self) -> Result<SimObject, Error> {
+        let render_object = self.render.as_ref().map(SceneRender::build);
+
+        let physics_object = self.physics.as_ref().map(|physics| -> Result<PhysicsObject, Error> {
+            let collider = match physics.collider {
+                SceneCollider::Circle { radius } => ColliderType::Circle { radius },
+                SceneCollider::Rectangle { width, height } => ColliderType::Rectangle { width, height },
+                SceneCollider::Line { length } => ColliderType::Line { length },
+            };
+            let mut phys_obj = PhysicsObject::new(collider, physics.mass, Vector::new(physics.velocity.0, physics.velocity.1), Vector::new(0.0, 0.0))?;
+            phys_obj.angular_velocity = physics.angular_velocity;
+            phys_obj.linear_drag = physics.linear_drag;
+            phys_obj.angular_damping = physics.angular_damping;
+            phys_obj.quadratic_drag = physics.quadratic_drag;
+            phys_obj.layer = physics.layer;
+            phys_obj.charge = physics.charge;
+            phys_obj.restitution = physics.restitution;
+            Ok(phys_obj)
+        }).transpose()?;
+
+        let mut object = SimObject::new(self.x, self.y, self.rotation, render_object, physics_object, None, None);
+        object.name = self.name.clone();
+        object.tags = self.tags.iter().cloned().collect();
+        Ok(object)
+    }
+}
+
+impl SceneRender {
+    pub fn capture(render_object: &RenderObject) -> SceneRender {
+        SceneRender {
+            color: (render_object.color.r, render_object.color.g, render_object.color.b, render_object.color.a),
+            z_index: render_object.z_index,
+            render_type: match &render_object.render_type {
+                RenderType::Line { magnitude } => SceneRenderType::Line { magnitude: *magnitude },
+                RenderType::Circle { radius, segments } => SceneRenderType::Circle { radius: *radius, segments: *segments },
+                RenderType::Polygon { vertices } => SceneRenderType::Polygon { vertices: vertices.clone() },
+                RenderType::Point => SceneRenderType::Point,
+                RenderType::Rectangle { width, height, filled } => SceneRenderType::Rectangle { width: *width, height: *height, filled: *filled },
+                RenderType::Texture { .. } | RenderType::None {} => SceneRenderType::None,
+            },
+        }
+    }
+
+    pub fn build(&self) -> RenderObject {
+        let (r, g, b, a) = self.color;
+        let mut render_object = RenderObject::new(sdl2::pixels::Color::RGBA(r, g, b, a), match &self.render_type {
+            SceneRenderType::Line { magnitude } => RenderType::Line { magnitude: *magnitude },
+            SceneRenderType::Circle { radius, segments } => RenderType::Circle { radius: *radius, segments: *segments },
+            SceneRenderType::Polygon { vertices } => RenderType::Polygon { vertices: vertices.clone() },
+            SceneRenderType::Point => RenderType::Point,
+            SceneRenderType::Rectangle { width, height, filled } => RenderType::Rectangle { width: *width, height: *height, filled: *filled },
+            SceneRenderType::None => RenderType::None {},
+        });
+        render_object.z_index = self.z_index;
+        render_object
+    }
+}
+
+// Sorted so saved files don't change between runs
+pub(crate) fn sorted_tags(object: &SimObject) -> Vec<String> {
+    let mut tags: Vec<String> = object.tags.iter().cloned().collect();
+    tags.sort();
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use physics::{ColliderType, Physics, PhysicsObject};
+
+    fn scene_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("physics_sim_{}_{}.ron", name, std::process::id()))
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let mut simulation = Simulation::headless(8000, Physics::new(Vector::new(0.0, -3.5))).unwrap();
+        let phys_obj = PhysicsObject::new(ColliderType::Circle { radius: 2.0 }, 4.0, Vector::new(1.0, -2.0), Vector::ZERO).unwrap();
+        let parent = simulation.add_object(SimObject::new(10.0, 20.0, 45.0, None, Some(phys_obj), None, None).with_name("parent").with_tag("b").with_tag("a")).unwrap();
+        let child = simulation.add_object(SimObject::new(1.0, 2.0, 0.0, None, None, None, None).with_name("child")).unwrap();
+        simulation.set_parent(child, Some(parent)).unwrap();
+
+        let path = scene_path("round_trip");
+        simulation.save_scene(&path).unwrap();
+        let mut loaded = Simulation::headless(1, Physics::new(Vector::ZERO)).unwrap();
+        loaded.load_scene(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.time_step, 8000);
+        assert_eq!(loaded.physics.get_gravity(), Vector::new(0.0, -3.5));
+        assert_eq!(loaded.objects.len(), 2);
+
+        let parent = loaded.objects.get(loaded.find_by_name("parent").unwrap()).unwrap();
+        assert!(parent.has_tag("a") && parent.has_tag("b"));
+        assert_eq!(parent.transform, Transform::new(Vector::new(10.0, 20.0), Rot::from_degrees(45.0)));
+        let phys_obj = parent.physics_object.as_ref().unwrap();
+        assert_eq!(phys_obj.get_mass(), 4.0);
+        assert_eq!(phys_obj.get_velocity(), Vector::new(1.0, -2.0));
+
+        let child = loaded.objects.get(loaded.find_by_name("child").unwrap()).unwrap();
+        assert_eq!(child.parent, Some(parent.id));
+        assert_eq!(child.transform.position, Vector::new(1.0, 2.0));
+    }
+
+    #[test]
+    fn load_rejects_invalid_time_step() {
+        let path = scene_path("invalid_time_step");
+        std::fs::write(&path, "(gravity: (0.0, 0.0), time_step: 0)").unwrap();
+        let mut simulation = Simulation::headless(1, Physics::new(Vector::ZERO)).unwrap();
+        let result = simulation.load_scene(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(Error::InvalidTimeStep(0))));
+    }
+
+    #[test]
+    fn load_reports_missing_and_malformed_files() {
+        let mut simulation = Simulation::headless(1, Physics::new(Vector::ZERO)).unwrap();
+        assert!(matches!(simulation.load_scene(scene_path("missing")), Err(Error::Io(_))));
+
+        let path = scene_path("malformed");
+        std::fs::write(&path, "(gravity: ").unwrap();
+        let result = simulation.load_scene(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(Error::Parse(_))));
+    }
+}
@@ -0,0 +1,252 @@
+use serde::{Deserialize, Serialize};
+use std::time;
+
+use crate::*;
+use commands::Context;
+
+pub type TimerCallback = Box<dyn TimerFn>;
+
+/// Implemented for every Clone closure
+pub trait TimerFn: FnMut(&mut Context) {
+    fn clone_box(&self) -> TimerCallback;
+}
+
+impl<F: FnMut(&mut Context) + Clone + 'static> TimerFn for F {
+    fn clone_box(&self) -> TimerCallback {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for TimerCallback {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+/// Cancels a scheduled callback
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TimerHandle(u64);
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum Schedule {
+    After(time::Duration),
+    Every(time::Duration),
+    AfterSteps(u64),
+}
+
+struct Timer {
+    id: u64,
+    schedule: Schedule,
+    due_time: time::Duration,
+    due_step: u64,
+    callback: TimerCallback,
+}
+
+/// Pending timers and the clock, captured by snapshots
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SchedulerState {
+    pub time: time::Duration,
+    pub step: u64,
+    pub next_id: u64,
+    pub timers: Vec<TimerState>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TimerState {
+    pub id: u64,
+    pub schedule: Schedule,
+    pub due_time: time::Duration,
+    pub due_step: u64,
+    // None when read from disk
+    #[serde(skip)]
+    pub callback: Option<TimerCallback>,
+}
+
+/// Runs callbacks on simulated time, so timers pause with the simulation and replay deterministically
+#[derive(Default)]
+pub struct Scheduler {
+    timers: Vec<Timer>,
+    next_id: u64,
+    // Clock as of the last fixed step
+    time: time::Duration,
+    step: u64,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler::default()
+    }
+
+    pub fn after<F: FnMut(&mut Context) + Clone + 'static>(&mut self, delay: time::Duration, callback: F) -> TimerHandle {
+        self.add(Schedule::After(delay), Box::new(callback))
+    }
+
+    /// A zero interval runs once every fixed step
+    pub fn every<F: FnMut(&mut Context) + Clone + 'static>(&mut self, interval: time::Duration, callback: F) -> TimerHandle {
+        self.add(Schedule::Every(interval), Box::new(callback))
+    }
+
+    pub fn after_steps<F: FnMut(&mut Context) + Clone + 'static>(&mut self, steps: u64, callback: F) -> TimerHandle {
+        self.add(Schedule::AfterSteps(steps), Box::new(callback))
+    }
+
+    pub fn add(&mut self, schedule: Schedule, callback: TimerCallback) -> TimerHandle {
+        let (due_time, due_step) = match schedule {
+            Schedule::After(delay) | Schedule::Every(delay) => (self.time + delay, 0),
+            Schedule::AfterSteps(steps) => (time::Duration::ZERO, self.step + steps),
+        };
+
+        self.next_id += 1;
+        self.timers.push(Timer { id: self.next_id, schedule, due_time, due_step, callback });
+        TimerHandle(self.next_id)
+    }
+
+    /// Returns false if the timer already ran or was cancelled
+    pub fn cancel(&mut self, handle: TimerHandle) -> bool {
+        let count = self.timers.len();
+        self.timers.retain(|timer| timer.id != handle.0);
+        self.timers.len() != count
+    }
+
+    pub fn is_pending(&self, handle: TimerHandle) -> bool {
+        self.timers.iter().any(|timer| timer.id == handle.0)
+    }
+
+    pub fn clear(&mut self) {
+        self.timers.clear();
+    }
+
+    pub fn save_state(&self) -> SchedulerState {
+        SchedulerState {
+            time: self.time,
+            step: self.step,
+            next_id: self.next_id,
+            timers: self.timers.iter().map(|timer| TimerState {
+                id: timer.id,
+                schedule: timer.schedule,
+                due_time: timer.due_time,
+                due_step: timer.due_step,
+                callback: Some(timer.callback.clone()),
+            }).collect(),
+        }
+    }
+
+    /// Timers without a stored callback take the one of the live timer with the same id,
+    /// they are dropped if that timer already ran
+    pub fn restore_state(&mut self, state: &SchedulerState) {
+        let mut live = std::mem::take(&mut self.timers);
+        self.timers = state.timers.iter().filter_map(|timer| {
+            let callback = match timer.callback {
+                Some(ref callback) => callback.clone(),
+                None => {
+                    let index = live.iter().position(|live| live.id == timer.id)?;
+                    live.swap_remove(index).callback
+                },
+            };
+            Some(Timer { id: timer.id, schedule: timer.schedule, due_time: timer.due_time, due_step: timer.due_step, callback })
+        }).collect();
+        self.time = state.time;
+        self.step = state.step;
+        self.next_id = state.next_id;
+    }
+
+    /// Moves the clock to a restored time, keeping how far away every pending timer is
+    pub(crate) fn rebase(&mut self, simulation_time: time::Duration, step_count: u64) {
+        for timer in self.timers.iter_mut() {
+            timer.due_time = simulation_time + timer.due_time.saturating_sub(self.time);
+            timer.due_step = step_count + timer.due_step.saturating_sub(self.step);
+        }
+        self.time = simulation_time;
+        self.step = step_count;
+    }
+
+    /// Called by `Simulation` after every fixed step, timers run in the order they were added
+    pub fn run(&mut self, simulation_time: time::Duration, step_count: u64, context: &mut Context) {
+        self.time = simulation_time;
+        self.step = step_count;
+
+        self.timers.retain_mut(|timer| match timer.schedule {
+            Schedule::After(_) => {
+                if timer.due_time > simulation_time { return true; }
+                (timer.callback)(context);
+                false
+            },
+            Schedule::AfterSteps(_) => {
+                if timer.due_step > step_count { return true; }
+                (timer.callback)(context);
+                false
+            },
+            Schedule::Every(interval) => {
+                // Catches up when the interval is shorter than the step
+                while timer.due_time <= simulation_time {
+                    (timer.callback)(context);
+                    if interval.is_zero() {
+                        timer.due_time = simulation_time + time::Duration::from_nanos(1);
+                    } else {
+                        timer.due_time += interval;
+                    }
+                }
+                true
+            },
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use physics::Physics;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn counter(scheduled: &Rc<Cell<u32>>) -> impl FnMut(&mut Context) + Clone {
+        let count = Rc::clone(scheduled);
+        move |_context: &mut Context| count.set(count.get() + 1)
+    }
+
+    fn step(simulation: &mut Simulation, steps: usize) {
+        for _ in 0..steps {
+            simulation.step_once();
+        }
+    }
+
+    #[test]
+    fn restore_rewinds_timers() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::ZERO)).unwrap();
+        let after = Rc::new(Cell::new(0));
+        let after_steps = Rc::new(Cell::new(0));
+        simulation.scheduler.after(time::Duration::from_millis(15), counter(&after));
+        simulation.scheduler.after_steps(3, counter(&after_steps));
+        step(&mut simulation, 1);
+        let snapshot = simulation.snapshot();
+
+        step(&mut simulation, 5);
+        assert_eq!((after.get(), after_steps.get()), (1, 1));
+
+        // Both timers are pending again and due at the same steps as before
+        simulation.restore(&snapshot).unwrap();
+        assert_eq!((after.get(), after_steps.get()), (1, 1));
+        step(&mut simulation, 1);
+        assert_eq!((after.get(), after_steps.get()), (2, 1));
+        step(&mut simulation, 1);
+        assert_eq!((after.get(), after_steps.get()), (2, 2));
+        step(&mut simulation, 5);
+        assert_eq!((after.get(), after_steps.get()), (2, 2));
+    }
+
+    #[test]
+    fn snapshot_from_disk_keeps_live_callbacks() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::ZERO)).unwrap();
+        let fired = Rc::new(Cell::new(0));
+        let handle = simulation.scheduler.after_steps(2, counter(&fired));
+        let loaded = snapshot::round_trip(&simulation.snapshot());
+
+        step(&mut simulation, 1);
+        simulation.restore(&loaded).unwrap();
+        assert!(simulation.scheduler.is_pending(handle));
+        step(&mut simulation, 1);
+        assert_eq!(fired.get(), 0);
+        step(&mut simulation, 1);
+        assert_eq!(fired.get(), 1);
+    }
+}
@@ -0,0 +1,309 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time;
+
+use crate::*;
+use gas::GasContainerState;
+use graphics::RenderType;
+use particles::ParticleSystemState;
+use physics::Contact;
+use physics::PhysicsObject;
+use scheduler::SchedulerState;
+use scene::{sorted_tags, SceneRender};
+
+/// The complete dynamic state of a Simulation.
+/// Soft bodies, ropes, fluids and gas containers are matched by index, emitters by id,
+/// so a snapshot is restored into a simulation that was set up the same way.
+/// Behaviours, components and timer callbacks are cloned in, which is why they must be Clone.
+/// They can't be written to disk, a loaded snapshot keeps the live ones
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub step_count: u64,
+    pub simulation_time: time::Duration,
+    pub fixed_delta_time: time::Duration,
+    pub gravity: Vector,
+    pub objects: Vec<ObjectSnapshot>,
+    // Id counter of the registry, so replays hand out the same ids
+    #[serde(default)]
+    pub next_id: u128,
+    pub contacts: Vec<Contact>,
+    pub soft_bodies: Vec<SoftBodyState>,
+    pub verlet_bodies: Vec<VerletBodyState>,
+    pub fluids: Vec<Vec<sph::SphParticle>>,
+    pub particles: ParticleSystemState,
+    pub gas_containers: Vec<GasContainerState>,
+    // None for snapshots saved before timers were captured
+    #[serde(default)]
+    pub scheduler: Option<SchedulerState>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ObjectSnapshot {
+    pub id: u128,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub x: f64,
+    pub y: f64,
+    pub rotation: f64,
+    // Textures can't be copied, restore keeps the live texture of the object with the same id
+    pub render: Option<SceneRender>,
+    // Includes pending forces, acceleration and torque
+    pub physics: Option<PhysicsObject>,
+    #[serde(default)]
+    pub parent: Option<u128>,
+    // Scripts only survive in memory
+    #[serde(skip)]
+    pub update: Option<Callback>,
+    #[serde(skip)]
+    pub fixed_update: Option<Callback>,
+    // None when read from disk
+    #[serde(skip)]
+    pub behaviours: Option<Vec<Box<dyn behaviour::Behaviour>>>,
+    #[serde(skip)]
+    pub started_behaviours: usize,
+    #[serde(skip)]
+    pub components: Option<components::Components>,
+}
+
+impl ObjectSnapshot {
+    pub fn capture(object: &SimObject) -> ObjectSnapshot {
+        ObjectSnapshot {
+            id: object.id,
+            name: object.name.clone(),
+            tags: sorted_tags(object),
+            x: object.transform.position.x,
+            y: object.transform.position.y,
+            rotation: object.transform.rotation.degrees,
+            render: object.render_object.as_ref().map(SceneRender::capture),
+            physics: object.physics_object.clone(),
+            parent: object.parent,
+            update: object.update,
+            fixed_update: object.fixed_update,
+            behaviours: Some(object.behaviours.clone()),
+            started_behaviours: object.started_behaviours,
+            components: Some(object.components.clone()),
+        }
+    }
+
+    /// Rebuilds the object with its original id, behaviours and components are cloned again so the snapshot can be reused
+    pub fn build(&self) -> SimObject {
+        let mut object = SimObject::new(self.x, self.y, self.rotation, self.render.as_ref().map(SceneRender::build), self.physics.clone(), self.update, self.fixed_update);
+        object.id = self.id;
+        object.parent = self.parent;
+        object.name = self.name.clone();
+        object.tags = self.tags.iter().cloned().collect();
+        if let Some(ref behaviours) = self.behaviours {
+            object.behaviours = behaviours.clone();
+            object.started_behaviours = self.started_behaviours;
+        }
+        if let Some(ref components) = self.components {
+            object.components = components.clone();
+        }
+        object
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SoftBodyState {
+    pub points: Vec<softbody::PointMass>,
+    pub springs: Vec<softbody::Spring>,
+}
+
+// Constraints are included since tearing removes them
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VerletBodyState {
+    pub points: Vec<verlet::VerletPoint>,
+    pub constraints: Vec<verlet::DistanceConstraint>,
+    pub anchors: Vec<verlet::Anchor>,
+}
+
+impl Snapshot {
+    pub fn capture(simulation: &Simulation) -> Snapshot {
+        Snapshot {
+            step_count: simulation.step_count,
+            simulation_time: simulation.simulation_time,
+            fixed_delta_time: simulation.fixed_delta_time,
+            gravity: simulation.physics.get_gravity(),
+            objects: simulation.objects.iter().map(ObjectSnapshot::capture).collect(),
+            next_id: simulation.objects.get_next_id(),
+            contacts: simulation.physics.get_contacts().to_vec(),
+            soft_bodies: simulation.soft_bodies.iter().map(|soft_body| SoftBodyState {
+                points: soft_body.points.clone(),
+                springs: soft_body.springs.clone(),
+            }).collect(),
+            verlet_bodies: simulation.verlet_bodies.iter().map(|verlet_body| VerletBodyState {
+                points: verlet_body.points.clone(),
+                constraints: verlet_body.constraints.clone(),
+                anchors: verlet_body.anchors.clone(),
+            }).collect(),
+            fluids: simulation.fluids.iter().map(|fluid| fluid.particles.clone()).collect(),
+            particles: simulation.particles.save_state(),
+            gas_containers: simulation.gas_containers.iter().map(|gas_container| gas_container.save_state()).collect(),
+            scheduler: Some(simulation.scheduler.save_state()),
+        }
+    }
+
+    pub fn restore(&self, simulation: &mut Simulation) -> Result<(), Error> {
+        let mut ids = std::collections::HashSet::new();
+        if let Some(object) = self.objects.iter().find(|object| !ids.insert(object.id)) {
+            return Err(Error::DuplicateId(object.id));
+        }
+
+        let objects = self.objects.iter().map(|snapshot| {
+            let mut object = snapshot.build();
+
+            // Keep the texture from the live object since it can't be part of the snapshot
+            if let Some(live) = simulation.objects.get_by_id_mut(snapshot.id) {
+                // Behaviours and components rewind to their captured clones, a snapshot read from disk keeps the live ones
+                if snapshot.components.is_none() {
+                    object.components = std::mem::take(&mut live.components);
+                }
+                if snapshot.behaviours.is_none() {
+                    object.behaviours = std::mem::take(&mut live.behaviours);
+                    object.started_behaviours = live.started_behaviours;
+                }
+                if let Some(RenderType::Texture { .. }) = live.render_object.as_ref().map(|render_object| &render_object.render_type) {
+                    object.render_object = live.render_object.take();
+                }
+            }
+            object
+        }).collect();
+        // Objects that still exist keep their handles
+        let removed = simulation.objects.replace_all(objects)?;
+        simulation.destroy_replaced(removed);
+        simulation.objects.set_next_id(self.next_id);
+
+        simulation.step_count = self.step_count;
+        simulation.simulation_time = self.simulation_time;
+        simulation.fixed_delta_time = self.fixed_delta_time;
+        simulation.last_fixed_update_time = time::Instant::now();
+        simulation.physics.set_gravity(self.gravity);
+        simulation.physics.restore_contacts(self.contacts.clone());
+
+        for (soft_body, state) in simulation.soft_bodies.iter_mut().zip(&self.soft_bodies) {
+            soft_body.points = state.points.clone();
+            soft_body.springs = state.springs.clone();
+        }
+        for (verlet_body, state) in simulation.verlet_bodies.iter_mut().zip(&self.verlet_bodies) {
+            verlet_body.points = state.points.clone();
+            verlet_body.constraints = state.constraints.clone();
+            verlet_body.anchors = state.anchors.clone();
+        }
+        for (fluid, particles) in simulation.fluids.iter_mut().zip(&self.fluids) {
+            fluid.particles = particles.clone();
+        }
+        simulation.particles.restore_state(&self.particles);
+        for (gas_container, state) in simulation.gas_containers.iter_mut().zip(&self.gas_containers) {
+            gas_container.restore_state(state);
+        }
+        match self.scheduler {
+            Some(ref state) => simulation.scheduler.restore_state(state),
+            None => simulation.scheduler.rebase(self.simulation_time, self.step_count),
+        }
+
+        if simulation.physics.is_diagnostics_enabled() {
+            let locals = simulation.objects.enter_world_space();
+            simulation.physics.update_diagnostics(&simulation.objects, &simulation.soft_bodies);
+            simulation.objects.leave_world_space(locals);
+        }
+        Ok(())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Snapshot, Error> {
+        ron::from_str(&fs::read_to_string(path)?).map_err(|error| Error::Parse(error.to_string()))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let text = ron::to_string(self).map_err(|error| Error::Serialize(error.to_string()))?;
+        fs::write(path, text)?;
+        Ok(())
+    }
+}
+
+/// Saves to a temp file and loads it back, the file is removed before the caller can fail an assertion
+#[cfg(test)]
+pub(crate) fn round_trip(snapshot: &Snapshot) -> Snapshot {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let path = std::env::temp_dir().join(format!("physics_sim_snapshot_{}_{}.ron", std::process::id(), COUNT.fetch_add(1, Ordering::SeqCst)));
+    let saved = snapshot.save(&path);
+    let loaded = Snapshot::load(&path);
+    let _ = fs::remove_file(&path);
+    saved.unwrap();
+    loaded.unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use physics::{ColliderType, Physics};
+
+    fn ball_simulation() -> Simulation {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::new(0.0, -9.8))).unwrap();
+        let phys_obj = PhysicsObject::new(ColliderType::Circle { radius: 1.0 }, 2.0, Vector::new(3.0, 4.0), Vector::ZERO).unwrap();
+        simulation.add_object(SimObject::new(0.0, 100.0, 0.0, None, Some(phys_obj), None, None).with_name("ball")).unwrap();
+        simulation
+    }
+
+    fn positions(simulation: &Simulation) -> Vec<(u128, Vector, Vector)> {
+        simulation.objects.iter().map(|object| {
+            (object.id, object.transform.position, object.physics_object.as_ref().unwrap().get_velocity())
+        }).collect()
+    }
+
+    #[test]
+    fn restore_rewinds_objects_and_time() {
+        let mut simulation = ball_simulation();
+        simulation.step_once();
+        let snapshot = simulation.snapshot();
+        let before = positions(&simulation);
+
+        for _ in 0..5 {
+            simulation.step_once();
+        }
+        let after = positions(&simulation);
+        assert_ne!(before, after);
+
+        simulation.restore(&snapshot).unwrap();
+        assert_eq!(positions(&simulation), before);
+        assert_eq!(simulation.get_step_count(), 1);
+        assert_eq!(simulation.get_simulation_time(), time::Duration::from_micros(10000));
+
+        // Replaying the same steps lands on the same state
+        for _ in 0..5 {
+            simulation.step_once();
+        }
+        assert_eq!(positions(&simulation), after);
+    }
+
+    #[test]
+    fn restore_brings_back_removed_objects() {
+        let mut simulation = ball_simulation();
+        let ball = simulation.find_by_name("ball").unwrap();
+        let snapshot = simulation.snapshot();
+
+        simulation.remove_object(ball);
+        let extra = simulation.add_object(SimObject::new(5.0, 5.0, 0.0, None, None, None, None).with_name("extra")).unwrap();
+
+        simulation.restore(&snapshot).unwrap();
+        assert_eq!(simulation.objects.len(), 1);
+        assert!(simulation.find_by_name("ball").is_some());
+        assert!(!simulation.objects.contains(extra));
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let mut simulation = ball_simulation();
+        simulation.step_once();
+        let loaded = round_trip(&simulation.snapshot());
+
+        let mut restored = ball_simulation();
+        restored.restore(&loaded).unwrap();
+        assert_eq!(positions(&restored), positions(&simulation));
+        assert_eq!(restored.get_step_count(), simulation.get_step_count());
+    }
+}
@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+use std::time;
+
+use crate::*;
+
+pub struct SoftBody {
+    pub points: Vec<PointMass>,
+    pub springs: Vec<Spring>,
+    // Indices of the outline points in order, used for pressure and rendering
+    pub outline: Vec<usize>,
+    // nRT in P = nRT / A, 0 disables the pressure term
+    pub pressure: f64,
+    pub color: sdl2::pixels::Color,
+    // Collides with rigid colliders sharing a layer bit with this mask
+    pub layers: u32,
+    pub restitution: f64,
+    pub friction: f64,
+    // Substeps per fixed update, stiff springs need more
+    pub substeps: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PointMass {
+    pub position: Vector,
+    pub velocity: Vector,
+    pub mass: f64,
+    force: Vector,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Spring {
+    pub a: usize,
+    pub b: usize,
+    pub rest_length: f64,
+    pub stiffness: f64,
+    pub damping: f64,
+}
+
+impl PointMass {
+    pub fn new(position: Vector, mass: f64) -> PointMass {
+        PointMass {
+            position,
+            velocity: Vector::new(0.0, 0.0),
+            mass,
+            force: Vector::new(0.0, 0.0),
+        }
+    }
+}
+
+impl SoftBody {
+    pub fn new(points: Vec<PointMass>, springs: Vec<Spring>, outline: Vec<usize>) -> SoftBody {
+        SoftBody {
+            points,
+            springs,
+            outline,
+            pressure: 0.0,
+            color: sdl2::pixels::Color::RGB(255, 255, 255),
+            layers: u32::MAX,
+            restitution: 0.2,
+            friction: 0.3,
+            substeps: 8,
+        }
+    }
+
+    /// A closed ring of point masses, inflate it with `pressure` for balloons and tyres
+    pub fn ring(center: Vector, radius: f64, segments: usize, mass: f64, stiffness: f64, damping: f64) -> SoftBody {
+        let point_mass = mass / segments as f64;
+        let mut points = Vec::new();
+        for i in 0..segments {
+            let angle = i as f64 / segments as f64 * std::f64::consts::TAU;
+            points.push(PointMass::new(center + Vector::new(angle.cos(), angle.sin()) * radius, point_mass));
+        }
+
+        let mut body = SoftBody::new(points, Vec::new(), (0..segments).collect());
+        for i in 0..segments {
+            // Edges, plus a second ring of springs skipping one point to resist folding
+            body.connect(i, (i + 1) % segments, stiffness, damping);
+            body.connect(i, (i + 2) % segments, stiffness * 0.5, damping);
+        }
+        body
+    }
+
+    /// A solid grid of point masses with structural and shear springs, for jelly
+    pub fn grid(origin: Vector, columns: usize, rows: usize, spacing: f64, mass: f64, stiffness: f64, damping: f64) -> SoftBody {
+        let columns = columns.max(2);
+        let rows = rows.max(2);
+        let point_mass = mass / (columns * rows) as f64;
+        let index = |column: usize, row: usize| row * columns + column;
+
+        let mut points = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                points.push(PointMass::new(origin + Vector::new(column as f64, row as f64) * spacing, point_mass));
+            }
+        }
+
+        // Counter-clockwise around the edge of the grid
+        let mut outline = Vec::new();
+        outline.extend((0..columns).map(|column| index(column, 0)));
+        outline.extend((1..rows).map(|row| index(columns - 1, row)));
+        outline.extend((0..columns - 1).rev().map(|column| index(column, rows - 1)));
+        outline.extend((1..rows - 1).rev().map(|row| index(0, row)));
+
+        let mut body = SoftBody::new(points, Vec::new(), outline);
+        for row in 0..rows {
+            for column in 0..columns {
+                if column + 1 < columns { body.connect(index(column, row), index(column + 1, row), stiffness, damping); }
+                if row + 1 < rows { body.connect(index(column, row), index(column, row + 1), stiffness, damping); }
+                if column + 1 < columns && row + 1 < rows {
+                    body.connect(index(column, row), index(column + 1, row + 1), stiffness, damping);
+                    body.connect(index(column + 1, row), index(column, row + 1), stiffness, damping);
+                }
+            }
+        }
+        body
+    }
+
+    /// Adds a spring whose rest length is the current distance between the points
+    pub fn connect(&mut self, a: usize, b: usize, stiffness: f64, damping: f64) {
+        let rest_length = (self.points[b].position - self.points[a].position).get_mag();
+        self.springs.push(Spring { a, b, rest_length, stiffness, damping });
+    }
+
+    pub fn get_center(&self) -> Vector {
+        let mut center = Vector::new(0.0, 0.0);
+        for point in &self.points {
+            center += point.position;
+        }
+        center / self.points.len().max(1) as f64
+    }
+
+    /// Signed area enclosed by the outline, positive when counter-clockwise
+    pub fn get_area(&self) -> f64 {
+        let mut area = 0.0;
+        for i in 0..self.outline.len() {
+            let a = self.points[self.outline[i]].position;
+            let b = self.points[self.outline[(i + 1) % self.outline.len()]].position;
+            area += a.x * b.y - b.x * a.y;
+        }
+        area / 2.0
+    }
+
+    pub fn update(&mut self, objects: &[SimObject], gravity: Vector, fixed_delta_time: time::Duration) {
+        let substeps = self.substeps.max(1);
+        let dt = fixed_delta_time.as_secs_f64() / substeps as f64;
+
+        for _ in 0..substeps {
+            self.accumulate_forces(gravity);
+
+            // Semi-implicit Euler, v = u + a * t then s = v * t
+            for point in &mut self.points {
+                let old_position = point.position;
+                point.velocity += point.force / point.mass * dt;
+                point.position += point.velocity * dt;
+                resolve_collisions(objects, self.layers, self.restitution, self.friction, old_position, point);
+            }
+        }
+    }
+
+    fn accumulate_forces(&mut self, gravity: Vector) {
+        for point in &mut self.points {
+            point.force = gravity * point.mass;
+        }
+
+        // Hooke's law with damping along the spring, F = -k(x - x0) - c(v . d)
+        for spring in &self.springs {
+            let offset = self.points[spring.b].position - self.points[spring.a].position;
+            let length = offset.get_mag();
+            if length == 0.0 { continue; }
+
+            let direction = offset / length;
+            let relative_velocity = self.points[spring.b].velocity - self.points[spring.a].velocity;
+            let closing_speed = relative_velocity.dot(direction);
+            let force = direction * (spring.stiffness * (length - spring.rest_length) + spring.damping * closing_speed);
+
+            self.points[spring.a].force += force;
+            self.points[spring.b].force -= force;
+        }
+
+        if self.pressure != 0.0 && self.outline.len() >= 3 {
+            let area = self.get_area();
+            if area.abs() < f64::EPSILON { return; }
+
+            // P = nRT / A acting outwards on every edge, split between its two points
+            let pressure = self.pressure / area.abs();
+            let winding = area.signum();
+            for i in 0..self.outline.len() {
+                let a = self.outline[i];
+                let b = self.outline[(i + 1) % self.outline.len()];
+                let edge = self.points[b].position - self.points[a].position;
+                let normal = edge.perpendicular() * -winding;
+                let force = normal * (pressure / 2.0);
+
+                self.points[a].force += force;
+                self.points[b].force += force;
+            }
+        }
+    }
+
+    /// Outline in screen order, closed as RenderType::Polygon expects
+    pub fn get_vertices(&self) -> Vec<Vector> {
+        let mut vertices: Vec<Vector> = self.outline.iter().map(|&i| self.points[i].position).collect();
+        if let Some(&first) = vertices.first() {
+            vertices.push(first);
+        }
+        vertices
+    }
+}
+
+impl graphics::Render for SoftBody {
+    fn render(&self, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
+        let vertices = self.get_vertices();
+        if vertices.len() < 4 { return; }
+
+        // Polygon points are unsigned, so they are taken relative to the bottom left corner
+        // and the polygon is drawn with its center at that corner plus the center of the points
+        let min_x = vertices.iter().map(|vertex| vertex.x).fold(f64::INFINITY, f64::min).floor();
+        let min_y = vertices.iter().map(|vertex| vertex.y).fold(f64::INFINITY, f64::min).floor();
+        let points: Vec<(u32, u32)> = vertices.iter().map(|vertex| ((vertex.x - min_x) as u32, (vertex.y - min_y) as u32)).collect();
+        let center = graphics::polygon_center(&points);
+
+        let render_type = graphics::RenderType::Polygon { vertices: points };
+        render_type.render(canvas, self.color, 0.0, true, (min_x + center.0 as f64, min_y + center.1 as f64));
+    }
+}
+
+fn resolve_collisions(objects: &[SimObject], layers: u32, restitution: f64, friction: f64, old_position: Vector, point: &mut PointMass) {
+    for object in objects {
+        let phys_obj = match object.physics_object {
+            Some(ref phys_obj) => phys_obj,
+            None => continue,
+        };
+        if phys_obj.layer & layers == 0 { continue; }
+
+        let position = object.transform.position;
+        if let Some((contact, normal)) = particles::point_contact(phys_obj.get_collider(), position, object.transform.rotation.degrees, old_position, point.position) {
+            // Bounce the normal part and apply friction to the tangential part
+            let normal_speed = point.velocity.dot(normal);
+            if normal_speed < 0.0 {
+                let tangent_velocity = point.velocity - normal * normal_speed;
+                point.velocity = tangent_velocity * (1.0 - friction) - normal * (normal_speed * restitution);
+            }
+            point.position = contact + normal * 0.01;
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::time;
+
+use crate::*;
+
+/// Smoothed-particle hydrodynamics liquid, defaults are tuned for pixel scale units and a rest density of 1
+pub struct SphFluid {
+    pub particles: Vec<SphParticle>,
+    // Kernel radius h, also the neighbour grid cell size
+    pub smoothing_radius: f64,
+    pub particle_mass: f64,
+    pub rest_density: f64,
+    // Gas constant k in p = k(rho - rho0)
+    pub stiffness: f64,
+    pub viscosity: f64,
+    pub surface_tension: f64,
+    // Fraction of velocity kept when bouncing off a boundary
+    pub restitution: f64,
+    // Axis aligned container, none lets particles leave freely
+    pub bounds: Option<(Vector, Vector)>,
+    // Collides with static colliders sharing a layer bit with this mask
+    pub layers: u32,
+    pub substeps: u32,
+    pub color_mode: ColorMode,
+    // Point size in pixels
+    pub render_size: u32,
+    grid: HashMap<(i32, i32), Vec<usize>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SphParticle {
+    pub position: Vector,
+    pub velocity: Vector,
+    pub density: f64,
+    pub pressure: f64,
+    force: Vector,
+}
+
+pub enum ColorMode {
+    Pressure,
+    Velocity,
+}
+
+impl SphParticle {
+    pub fn new(position: Vector) -> SphParticle {
+        SphParticle {
+            position,
+            velocity: Vector::new(0.0, 0.0),
+            density: 0.0,
+            pressure: 0.0,
+            force: Vector::new(0.0, 0.0),
+        }
+    }
+}
+
+impl SphFluid {
+    pub fn new(smoothing_radius: f64) -> SphFluid {
+        let mut fluid = SphFluid {
+            particles: Vec::new(),
+            smoothing_radius,
+            particle_mass: 1.0,
+            rest_density: 1.0,
+            stiffness: 4e5,
+            viscosity: 60.0,
+            surface_tension: 0.0,
+            restitution: 0.5,
+            bounds: None,
+            layers: u32::MAX,
+            substeps: 8,
+            color_mode: ColorMode::Velocity,
+            render_size: 3,
+            grid: HashMap::new(),
+        };
+        fluid.particle_mass = fluid.rest_density / fluid.calc_lattice_density(1.0);
+        fluid
+    }
+
+    /// Density a particle would see inside a block from add_block with the given particle mass
+    fn calc_lattice_density(&self, particle_mass: f64) -> f64 {
+        let spacing = self.smoothing_radius * 0.5;
+        let h_sq = self.smoothing_radius.powi(2);
+        let poly6 = 4.0 / (PI * self.smoothing_radius.powi(8));
+
+        let mut density = 0.0;
+        for x in -2..=2 {
+            for y in -2..=2 {
+                let r_sq = ((x * x + y * y) as f64) * spacing.powi(2);
+                if r_sq < h_sq {
+                    density += particle_mass * poly6 * (h_sq - r_sq).powi(3);
+                }
+            }
+        }
+        density
+    }
+
+    /// Fills a rectangle with particles at half the kernel radius, which is the spacing the mass is calibrated for
+    pub fn add_block(&mut self, min: Vector, max: Vector) {
+        let spacing = self.smoothing_radius * 0.5;
+        let mut y = min.y;
+        while y <= max.y {
+            let mut x = min.x;
+            while x <= max.x {
+                self.particles.push(SphParticle::new(Vector::new(x, y)));
+                x += spacing;
+            }
+            y += spacing;
+        }
+    }
+
+    pub fn update(&mut self, objects: &[SimObject], gravity: Vector, fixed_delta_time: time::Duration) {
+        let substeps = self.substeps.max(1);
+        let dt = fixed_delta_time.as_secs_f64() / substeps as f64;
+
+        for _ in 0..substeps {
+            self.build_grid();
+            self.calc_density_pressure();
+            self.calc_forces(gravity);
+            self.integrate(objects, dt);
+        }
+    }
+
+    fn cell(&self, position: Vector) -> (i32, i32) {
+        ((position.x / self.smoothing_radius).floor() as i32, (position.y / self.smoothing_radius).floor() as i32)
+    }
+
+    fn build_grid(&mut self) {
+        for cell in self.grid.values_mut() {
+            cell.clear();
+        }
+        for i in 0..self.particles.len() {
+            let cell = self.cell(self.particles[i].position);
+            self.grid.entry(cell).or_default().push(i);
+        }
+        self.grid.retain(|_, cell| !cell.is_empty());
+    }
+
+    // Every particle in the 3x3 block of cells around the position, including the particle itself
+    fn neighbours(&self, position: Vector) -> Vec<usize> {
+        let (cx, cy) = self.cell(position);
+        let mut neighbours = Vec::new();
+        for x in cx - 1..=cx + 1 {
+            for y in cy - 1..=cy + 1 {
+                if let Some(cell) = self.grid.get(&(x, y)) {
+                    neighbours.extend_from_slice(cell);
+                }
+            }
+        }
+        neighbours
+    }
+
+    fn calc_density_pressure(&mut self) {
+        let h_sq = self.smoothing_radius.powi(2);
+        let poly6 = 4.0 / (PI * self.smoothing_radius.powi(8));
+
+        for i in 0..self.particles.len() {
+            let position = self.particles[i].position;
+            let mut density = 0.0;
+            for j in self.neighbours(position) {
+                let offset = self.particles[j].position - position;
+                let r_sq = offset.get_mag_squared();
+                if r_sq < h_sq {
+                    density += self.particle_mass * poly6 * (h_sq - r_sq).powi(3);
+                }
+            }
+
+            // Clamped at zero so particles never pull each other together through pressure
+            self.particles[i].density = density;
+            self.particles[i].pressure = (self.stiffness * (density - self.rest_density)).max(0.0);
+        }
+    }
+
+    fn calc_forces(&mut self, gravity: Vector) {
+        let h = self.smoothing_radius;
+        let h_sq = h.powi(2);
+        let spiky_grad = -30.0 / (PI * h.powi(5));
+        let visc_lap = 40.0 / (PI * h.powi(5));
+        let poly6_grad = -24.0 / (PI * h.powi(8));
+        let poly6_lap = -24.0 / (PI * h.powi(8));
+
+        for i in 0..self.particles.len() {
+            let particle = &self.particles[i];
+            let mut pressure_force = Vector::new(0.0, 0.0);
+            let mut viscosity_force = Vector::new(0.0, 0.0);
+            // Colour field gradient and laplacian for surface tension
+            let mut normal = Vector::new(0.0, 0.0);
+            let mut curvature = 0.0;
+
+            for j in self.neighbours(particle.position) {
+                let other = &self.particles[j];
+                let offset = particle.position - other.position;
+                let r_sq = offset.get_mag_squared();
+                if r_sq >= h_sq || other.density == 0.0 { continue; }
+
+                let volume = self.particle_mass / other.density;
+                normal += offset * (volume * poly6_grad * (h_sq - r_sq).powi(2));
+                curvature += volume * poly6_lap * (h_sq - r_sq) * (3.0 * h_sq - 7.0 * r_sq);
+
+                if i == j { continue; }
+                let r = r_sq.sqrt();
+                if r == 0.0 { continue; }
+
+                // Symmetric pressure term so the forces are equal and opposite
+                pressure_force += offset / r * (-volume * (particle.pressure + other.pressure) / 2.0 * spiky_grad * (h - r).powi(2));
+                viscosity_force += (other.velocity - particle.velocity) * (self.viscosity * volume * visc_lap * (h - r));
+            }
+
+            let mut surface_force = Vector::new(0.0, 0.0);
+            let normal_length = normal.get_mag();
+            if self.surface_tension > 0.0 && normal_length > 0.1 / h {
+                surface_force = normal * (-self.surface_tension * curvature / normal_length);
+            }
+
+            self.particles[i].force = pressure_force + viscosity_force + surface_force + gravity * particle.density;
+        }
+    }
+
+    fn integrate(&mut self, objects: &[SimObject], dt: f64) {
+        for particle in &mut self.particles {
+            if particle.density == 0.0 { continue; }
+
+            // a = F / rho, the forces above are per unit volume
+            let old_position = particle.position;
+            particle.velocity += particle.force / particle.density * dt;
+            particle.position += particle.velocity * dt;
+
+            for object in objects {
+                let phys_obj = match object.physics_object {
+                    Some(ref phys_obj) => phys_obj,
+                    None => continue,
+                };
+                if phys_obj.layer & self.layers == 0 { continue; }
+
+                let position = object.transform.position;
+                if let Some((contact, normal)) = particles::point_contact(phys_obj.get_collider(), position, object.transform.rotation.degrees, old_position, particle.position) {
+                    let normal_speed = particle.velocity.dot(normal);
+                    if normal_speed < 0.0 {
+                        particle.velocity -= normal * ((1.0 + self.restitution) * normal_speed);
+                    }
+                    particle.position = contact + normal * 0.01;
+                }
+            }
+
+            if let Some((min, max)) = self.bounds {
+                if particle.position.x < min.x { particle.position.x = min.x; particle.velocity.x *= -self.restitution; }
+                if particle.position.x > max.x { particle.position.x = max.x; particle.velocity.x *= -self.restitution; }
+                if particle.position.y < min.y { particle.position.y = min.y; particle.velocity.y *= -self.restitution; }
+                if particle.position.y > max.y { particle.position.y = max.y; particle.velocity.y *= -self.restitution; }
+            }
+        }
+    }
+}
+
+impl graphics::Render for SphFluid {
+    fn render(&self, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
+        let height = canvas.output_size().unwrap().1 as i32;
+
+        // Normalise against the largest value this frame so the gradient always spans blue to red
+        let value = |particle: &SphParticle| match self.color_mode {
+            ColorMode::Pressure => particle.pressure,
+            ColorMode::Velocity => particle.velocity.get_mag(),
+        };
+        let max_value = self.particles.iter().map(value).fold(0.0, f64::max).max(f64::EPSILON);
+
+        for particle in &self.particles {
+            let t = (value(particle) / max_value).clamp(0.0, 1.0);
+            canvas.set_draw_color(sdl2::pixels::Color::RGB((t * 255.0) as u8, (t * 100.0) as u8, ((1.0 - t) * 255.0) as u8));
+
+            let x = particle.position.x as i32 - self.render_size as i32 / 2;
+            // Invert the y axis to match the physics
+            let y = height - particle.position.y as i32 - self.render_size as i32 / 2;
+            canvas.fill_rect(sdl2::rect::Rect::new(x, y, self.render_size, self.render_size)).unwrap();
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use std::time;
+
+use crate::*;
+
+/// Position based rope or cloth, points store their previous position instead of a velocity
+pub struct VerletBody {
+    pub points: Vec<VerletPoint>,
+    pub constraints: Vec<DistanceConstraint>,
+    pub anchors: Vec<Anchor>,
+    // Constraint passes per fixed update, more is stiffer
+    pub iterations: u32,
+    // Fraction of velocity lost per step
+    pub damping: f64,
+    // Constraints break once stretched past rest_length * tear_threshold, 0 never tears
+    pub tear_threshold: f64,
+    pub color: sdl2::pixels::Color,
+    // Collides with rigid colliders sharing a layer bit with this mask
+    pub layers: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VerletPoint {
+    pub position: Vector,
+    old_position: Vector,
+    pub pinned: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DistanceConstraint {
+    pub a: usize,
+    pub b: usize,
+    pub rest_length: f64,
+}
+
+// Keeps a point fixed to a SimObject, offset is in the object's local space
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Anchor {
+    pub point: usize,
+    pub object_id: u128,
+    pub offset: Vector,
+}
+
+impl VerletPoint {
+    pub fn new(position: Vector) -> VerletPoint {
+        VerletPoint {
+            position,
+            old_position: position,
+            pinned: false,
+        }
+    }
+
+    pub fn get_velocity(&self, fixed_delta_time: time::Duration) -> Vector {
+        (self.position - self.old_position) / fixed_delta_time.as_secs_f64()
+    }
+}
+
+impl VerletBody {
+    pub fn new(points: Vec<VerletPoint>) -> VerletBody {
+        VerletBody {
+            points,
+            constraints: Vec::new(),
+            anchors: Vec::new(),
+            iterations: 8,
+            damping: 0.01,
+            tear_threshold: 0.0,
+            color: sdl2::pixels::Color::RGB(255, 255, 255),
+            layers: u32::MAX,
+        }
+    }
+
+    /// A chain of points from start to end, the first point is pinned
+    pub fn rope(start: Vector, end: Vector, segments: usize) -> VerletBody {
+        let segments = segments.max(1);
+        let points = (0..=segments).map(|i| VerletPoint::new(start + (end - start) * (i as f64 / segments as f64))).collect();
+
+        let mut body = VerletBody::new(points);
+        for i in 0..segments {
+            body.connect(i, i + 1);
+        }
+        body.points[0].pinned = true;
+        body
+    }
+
+    /// A grid hanging down from origin, the top row is pinned
+    pub fn cloth(origin: Vector, columns: usize, rows: usize, spacing: f64) -> VerletBody {
+        let columns = columns.max(1);
+        let rows = rows.max(1);
+        let index = |column: usize, row: usize| row * columns + column;
+
+        let mut points = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                let mut point = VerletPoint::new(origin + Vector::new(column as f64 * spacing, -(row as f64) * spacing));
+                point.pinned = row == 0;
+                points.push(point);
+            }
+        }
+
+        let mut body = VerletBody::new(points);
+        for row in 0..rows {
+            for column in 0..columns {
+                if column + 1 < columns { body.connect(index(column, row), index(column + 1, row)); }
+                if row + 1 < rows { body.connect(index(column, row), index(column, row + 1)); }
+            }
+        }
+        body
+    }
+
+    /// Adds a constraint whose rest length is the current distance between the points
+    pub fn connect(&mut self, a: usize, b: usize) {
+        let rest_length = (self.points[b].position - self.points[a].position).get_mag();
+        self.constraints.push(DistanceConstraint { a, b, rest_length });
+    }
+
+    pub fn attach(&mut self, point: usize, object: &SimObject, offset: Vector) {
+        self.anchors.push(Anchor { point, object_id: object.id, offset });
+    }
+
+    pub fn detach(&mut self, object_id: u128) {
+        self.anchors.retain(|anchor| anchor.object_id != object_id);
+    }
+
+    pub fn update(&mut self, objects: &registry::ObjectRegistry, gravity: Vector, fixed_delta_time: time::Duration) {
+        let dt = fixed_delta_time.as_secs_f64();
+
+        // x' = x + (x - x_old) + a * t^2
+        for point in &mut self.points {
+            if point.pinned { continue; }
+
+            let velocity = (point.position - point.old_position) * (1.0 - self.damping);
+            point.old_position = point.position;
+            point.position += velocity + gravity * dt.powi(2);
+        }
+
+        // Objects don't move during the constraint passes, so anchor targets are looked up once
+        let targets: Vec<(usize, Vector)> = self.anchors.iter().filter_map(|anchor| {
+            let object = objects.get_by_id(anchor.object_id)?;
+            Some((anchor.point, object.transform.apply(anchor.offset)))
+        }).collect();
+
+        for _ in 0..self.iterations.max(1) {
+            self.apply_anchors(&targets);
+            self.satisfy_constraints();
+            self.resolve_collisions(objects);
+        }
+
+        if self.tear_threshold > 0.0 {
+            let points = &self.points;
+            let tear_threshold = self.tear_threshold;
+            self.constraints.retain(|constraint| {
+                (points[constraint.b].position - points[constraint.a].position).get_mag() <= constraint.rest_length * tear_threshold
+            });
+        }
+    }
+
+    fn is_fixed(&self, point: usize) -> bool {
+        self.points[point].pinned || self.anchors.iter().any(|anchor| anchor.point == point)
+    }
+
+    fn apply_anchors(&mut self, targets: &[(usize, Vector)]) {
+        for &(point, position) in targets {
+            let point = &mut self.points[point];
+            point.old_position = point.position;
+            point.position = position;
+        }
+    }
+
+    fn satisfy_constraints(&mut self) {
+        for i in 0..self.constraints.len() {
+            let (a, b, rest_length) = (self.constraints[i].a, self.constraints[i].b, self.constraints[i].rest_length);
+            let offset = self.points[b].position - self.points[a].position;
+            let length = offset.get_mag();
+            if length == 0.0 { continue; }
+
+            // Move both ends towards the rest length, fixed ends don't move
+            let correction = offset * ((length - rest_length) / length);
+            match (self.is_fixed(a), self.is_fixed(b)) {
+                (false, false) => {
+                    self.points[a].position += correction * 0.5;
+                    self.points[b].position -= correction * 0.5;
+                },
+                (false, true) => self.points[a].position += correction,
+                (true, false) => self.points[b].position -= correction,
+                (true, true) => {},
+            }
+        }
+    }
+
+    fn resolve_collisions(&mut self, objects: &[SimObject]) {
+        for object in objects {
+            let phys_obj = match object.physics_object {
+                Some(ref phys_obj) => phys_obj,
+                None => continue,
+            };
+            if phys_obj.layer & self.layers == 0 { continue; }
+
+            let position = object.transform.position;
+            for point in &mut self.points {
+                if point.pinned { continue; }
+
+                if let Some((contact, normal)) = particles::point_contact(phys_obj.get_collider(), position, object.transform.rotation.degrees, point.old_position, point.position) {
+                    point.position = contact + normal * 0.01;
+                }
+            }
+        }
+    }
+}
+
+impl graphics::Render for VerletBody {
+    fn render(&self, canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
+        let height = canvas.output_size().unwrap().1 as i32;
+        canvas.set_draw_color(self.color);
+        for constraint in &self.constraints {
+            let a = self.points[constraint.a].position;
+            let b = self.points[constraint.b].position;
+            // Invert the y axis to match the physics
+            canvas.draw_line(
+                (a.x as i32, height - a.y as i32),
+                (b.x as i32, height - b.y as i32)
+            ).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use physics::Physics;
+
+    #[test]
+    fn anchored_point_follows_its_object() {
+        let mut simulation = Simulation::headless(10000, Physics::new(Vector::new(0.0, -9.8))).unwrap();
+        let handle = simulation.add_object(SimObject::new(0.0, 0.0, 0.0, None, None, None, None)).unwrap();
+        let mut rope = VerletBody::rope(Vector::ZERO, Vector::new(0.0, -10.0), 5);
+        rope.attach(0, simulation.objects.get(handle).unwrap(), Vector::new(1.0, 0.0));
+        simulation.verlet_bodies.push(rope);
+
+        simulation.objects.get_mut(handle).unwrap().transform.position = Vector::new(20.0, 30.0);
+        simulation.step_once();
+        assert_eq!(simulation.verlet_bodies[0].points[0].position, Vector::new(21.0, 30.0));
+    }
+}